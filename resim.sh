@@ -29,7 +29,7 @@ echo Reservation NFT: $reservation
 echo Arbitrator badge: $arbitrator_badge
 
 echo
-resim call-method ${component} new_user >$OUTPUTFILE || ( cat $OUTPUTFILE ; exit 1 )
+resim run manifests/new_user.rtm >$OUTPUTFILE || ( cat $OUTPUTFILE ; exit 1 )
 export user_id=$(grep -A 1 "ResAddr: $user_badge" $OUTPUTFILE | tail -n 1 | cut -d '#' -f 2)
 echo "User created, NFT #${user_id}# received (should be 1)"
 
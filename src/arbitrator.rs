@@ -1,29 +1,281 @@
 use scrypto::prelude::*;
+use scrypto::prelude::rust::cmp;
 
 #[derive(ScryptoSbor, ScryptoEvent)]
 pub struct NewArbitratorEvent {
     arbitrator_id: u64,
+    user_id: u64,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ArbitratorConflictEvent {
+    arbitrator_id: u64,
+    user_id: u64,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct StakingSettingsEvent {
+    settings: StakingSettings,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ArbitratorStakeEvent {
+    arbitrator_id: u64,
+    staked: Decimal,
+    unstaking: Decimal,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ArbitratorSlashEvent {
+    arbitrator_id: u64,
+    reservation_id: u64,
+    amount: Decimal,
 }
 
 #[derive(Debug, ScryptoSbor, NonFungibleData)]
 pub struct Arbitrator {
     pub id: u64,
+    pub user_id: u64,
+    // Users whose disputes the arbitrator can't decide
+    #[mutable]
+    pub conflicts: Vec<u64>,
 }
 
 impl Arbitrator {
 
     pub fn new(
         id: u64,
+        user_id: u64,
     ) -> Arbitrator {
 
         Runtime::emit_event(
             NewArbitratorEvent {
                 arbitrator_id: id,
+                user_id: user_id,
             }
         );
 
         Self {
             id: id,
+            user_id: user_id,
+            conflicts: vec![],
+        }
+    }
+
+    pub fn declare_conflict(
+        &mut self,
+        user_id: u64,
+    ) {
+        assert!(
+            !self.conflicts.contains(&user_id),
+            "Conflict already declared",
+        );
+
+        self.conflicts.push(user_id);
+
+        Runtime::emit_event(
+            ArbitratorConflictEvent {
+                arbitrator_id: self.id,
+                user_id: user_id,
+            }
+        );
+    }
+
+    pub fn is_conflicted(
+        &self,
+        customer_id: u64,
+        owner_id: u64,
+    ) -> bool {
+        self.user_id == customer_id || self.user_id == owner_id ||
+            self.conflicts.contains(&customer_id) || self.conflicts.contains(&owner_id)
+    }
+}
+
+#[derive(Debug, ScryptoSbor, Clone)]
+pub struct StakingSettings {
+    pub token: ResourceAddress,
+    // Stake needed to be assigned to new panels
+    pub min_stake: Decimal,
+    // Percentage of the stake lost for a missed vote or a vote far from the outcome
+    pub slash_percentage: Decimal,
+    // Maximum distance in percentage points between a consistent vote and the outcome
+    pub vote_tolerance: Decimal,
+    // Paid from the slashed stakes for each consistent vote
+    pub consistency_reward: Decimal,
+    // Seconds between an unstake request and the withdrawal
+    pub unstake_cooldown: i64,
+}
+
+impl StakingSettings {
+
+    pub fn new(
+        token: ResourceAddress,
+        min_stake: Decimal,
+        slash_percentage: Decimal,
+        vote_tolerance: Decimal,
+        consistency_reward: Decimal,
+        unstake_cooldown: i64,
+    ) -> StakingSettings {
+        assert!(
+            min_stake >= Decimal::ZERO && consistency_reward >= Decimal::ZERO,
+            "Negative amounts not allowed",
+        );
+        for percentage in [slash_percentage, vote_tolerance] {
+            assert!(
+                percentage >= Decimal::ZERO && percentage <= dec![100],
+                "Percentage out of 0-100 range",
+            );
+        }
+        assert!(
+            unstake_cooldown >= 0,
+            "Negative unstake_cooldown not allowed",
+        );
+
+        let settings = Self {
+            token: token,
+            min_stake: min_stake,
+            slash_percentage: slash_percentage,
+            vote_tolerance: vote_tolerance,
+            consistency_reward: consistency_reward,
+            unstake_cooldown: unstake_cooldown,
+        };
+
+        Runtime::emit_event(
+            StakingSettingsEvent {
+                settings: settings.clone(),
+            }
+        );
+
+        settings
+    }
+}
+
+// The tokens an arbitrator asked to unstake can still be slashed until they are withdrawn
+#[derive(ScryptoSbor)]
+pub struct Stake {
+    arbitrator_id: u64,
+    vault: Vault,
+    unstaking: Decimal,
+    unstake_time: i64,
+}
+
+impl Stake {
+
+    pub fn new(
+        arbitrator_id: u64,
+        token: ResourceAddress,
+    ) -> Stake {
+        Self {
+            arbitrator_id: arbitrator_id,
+            vault: Vault::new(token),
+            unstaking: Decimal::ZERO,
+            unstake_time: 0,
         }
     }
+
+    fn emit_event(
+        &self,
+    ) {
+        Runtime::emit_event(
+            ArbitratorStakeEvent {
+                arbitrator_id: self.arbitrator_id,
+                staked: self.vault.amount(),
+                unstaking: self.unstaking,
+            }
+        );
+    }
+
+    pub fn active_amount(
+        &self,
+    ) -> Decimal {
+        self.vault.amount() - self.unstaking
+    }
+
+    pub fn deposit(
+        &mut self,
+        bucket: Bucket,
+    ) {
+        self.vault.put(bucket);
+
+        self.emit_event();
+    }
+
+    pub fn request_unstake(
+        &mut self,
+        amount: Decimal,
+    ) {
+        assert!(
+            amount > Decimal::ZERO && amount <= self.active_amount(),
+            "Wrong amount",
+        );
+
+        self.unstaking += amount;
+        self.unstake_time = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+
+        self.emit_event();
+    }
+
+    pub fn unstake(
+        &mut self,
+        unstake_cooldown: i64,
+    ) -> Bucket {
+        assert!(
+            self.unstaking > Decimal::ZERO,
+            "Nothing to unstake",
+        );
+        let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+        assert!(
+            now >= self.unstake_time + unstake_cooldown,
+            "You can't unstake now",
+        );
+
+        let bucket = self.vault.take(self.unstaking);
+        self.unstaking = Decimal::ZERO;
+
+        self.emit_event();
+
+        bucket
+    }
+
+    pub fn slash(
+        &mut self,
+        reservation_id: u64,
+        slash_percentage: Decimal,
+    ) -> Bucket {
+        let bucket = self.vault.take_advanced(
+            self.vault.amount() * slash_percentage / dec![100],
+            WithdrawStrategy::Rounded(RoundingMode::ToZero),
+        );
+        self.unstaking = cmp::min(self.unstaking, self.vault.amount());
+
+        Runtime::emit_event(
+            ArbitratorSlashEvent {
+                arbitrator_id: self.arbitrator_id,
+                reservation_id: reservation_id,
+                amount: bucket.amount(),
+            }
+        );
+        self.emit_event();
+
+        bucket
+    }
+}
+
+// Deterministically picks up to size arbitrators out of the candidates, using the transaction hash
+// as seed
+pub fn select_panel(
+    mut candidates: Vec<u64>,
+    size: usize,
+    nonce: u64,
+) -> Vec<u64> {
+    let seed = Runtime::transaction_hash();
+    let mut panel = vec![];
+
+    while panel.len() < size && !candidates.is_empty() {
+        let random = hash(scrypto_encode(&(seed, nonce, panel.len() as u64)).unwrap());
+        let index = u64::from_le_bytes(random.lower_bytes()) % candidates.len() as u64;
+        panel.push(candidates.swap_remove(index as usize));
+    }
+
+    panel
 }
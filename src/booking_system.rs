@@ -1,8 +1,20 @@
 use scrypto::prelude::*;
+use scrypto::prelude::rust::cmp;
+use scrypto::engine::scrypto_env::ScryptoVmV1Api;
 use crate::user::*;
 use crate::item::*;
 use crate::reservation::*;
 use crate::arbitrator::*;
+use crate::fee::*;
+use crate::coupon::*;
+use crate::pricing::*;
+use crate::loyalty::*;
+use crate::rewards::*;
+use crate::review::*;
+use crate::guest_lists::*;
+use crate::moderation::*;
+use crate::organisation::*;
+use crate::dispute::*;
 
 #[blueprint]
 #[events(
@@ -16,41 +28,156 @@ use crate::arbitrator::*;
     ReservationRefundEvent,
     ReservationDisputeEvent,
     ReservationRefundOfferEvent,
+    ReservationCounterOfferEvent,
+    ReservationSettlementEvent,
+    ReservationArbitrationEvent,
+    ReservationGetPaymentEvent,
+    DisputeSettingsEvent,
+    DisputeDefaultOutcomeEvent,
+    SecurityDepositReturnEvent,
+    EvidenceSubmittedEvent,
+    ArbitratorPanelEvent,
+    ArbitratorPanelIncompleteEvent,
+    DisputeVoteCommitEvent,
     DisputeVoteEvent,
     DisputeVoteTerminatedEvent,
     NewArbitratorEvent,
+    ArbitratorConflictEvent,
+    StakingSettingsEvent,
+    ArbitratorStakeEvent,
+    ArbitratorSlashEvent,
+    NewCouponEvent,
+    CouponDisabledEvent,
+    PricingRulesEvent,
+    DynamicPricingEvent,
+    LoyaltyPointsCreditEvent,
+    LoyaltyPointsClaimEvent,
+    LoyaltyPointsRedeemEvent,
+    ReferralRewardEvent,
+    ReviewSubmittedEvent,
+    ProfileUpdateEvent,
+    UserVerificationEvent,
+    GuestBlocklistEvent,
+    GuestAllowlistEvent,
+    UserSuspensionEvent,
+    UserUnsuspensionEvent,
+    AppealEvent,
+    ItemDelistEvent,
+    ItemRelistEvent,
+    ItemDelegateEvent,
+    GuardianUpdateEvent,
+    UserBadgeRecoveryEvent,
+    NewOrganisationEvent,
+    OrganisationMemberEvent,
 )]
 mod booking_system {
 
     enable_method_auth! {
         roles {
             arbitrator => updatable_by: [OWNER];
+            verifier => updatable_by: [OWNER];
+            moderator => updatable_by: [OWNER];
         },
         methods {
             set_payment_delay => restrict_to: [OWNER];
             get_arbitrator_badge => restrict_to: [OWNER];
             set_min_arbitrators => restrict_to: [OWNER];
+            set_arbitrator_active => restrict_to: [OWNER];
+            set_arbitration_fee => restrict_to: [OWNER];
+            set_loser_pays_arbitration => restrict_to: [OWNER];
+            set_vote_aggregation => restrict_to: [OWNER];
+            set_staking_settings => restrict_to: [OWNER];
+            set_dispute_settings => restrict_to: [OWNER];
+            add_swap_component => restrict_to: [OWNER];
+            remove_swap_component => restrict_to: [OWNER];
+            set_platform_fee => restrict_to: [OWNER];
+            withdraw_treasury => restrict_to: [OWNER];
+            new_coupon => restrict_to: [OWNER];
+            disable_coupon => restrict_to: [OWNER];
+            set_loyalty_earn_rate => restrict_to: [OWNER];
+            set_loyalty_burn_rate => restrict_to: [OWNER];
+            set_referral_program => restrict_to: [OWNER];
+            set_user_verified => restrict_to: [verifier];
+            set_user_badges_soulbound => restrict_to: [OWNER];
+            set_registration_mode => restrict_to: [OWNER];
+            unlink_account => restrict_to: [OWNER];
+            unlink_identity => restrict_to: [OWNER];
+            recover_user_badge => restrict_to: [OWNER];
+            suspend_user => restrict_to: [moderator];
+            delist_item => restrict_to: [moderator];
+            unsuspend_user => restrict_to: [OWNER];
+            relist_item => restrict_to: [OWNER];
 
             new_user => PUBLIC;
+            claim_loyalty_points => PUBLIC;
+            deposit_loyalty_points => PUBLIC;
+            claim_referral_rewards => PUBLIC;
+            update_profile => PUBLIC;
+            set_guardian => PUBLIC;
+            recover_user_badge_as_guardian => PUBLIC;
+            appeal => PUBLIC;
 
             new_item => PUBLIC;
             add_or_modify_availability_interval => PUBLIC;
+            set_pricing_rules => PUBLIC;
+            set_dynamic_pricing => PUBLIC;
+            set_verified_guests_only => PUBLIC;
+            set_item_private => PUBLIC;
+            set_item_delegate => PUBLIC;
+            set_security_deposit => PUBLIC;
+            start_dispute_as_owner => PUBLIC;
+            submit_evidence_as_owner => PUBLIC;
+            new_organisation => PUBLIC;
+            set_organisation_member => PUBLIC;
+            withdraw_organisation_treasury => PUBLIC;
+            claim_owner_payouts => PUBLIC;
+            set_guest_blocked => PUBLIC;
+            set_guest_allowed => PUBLIC;
             reservation_cancellation_by_owner => PUBLIC;
             offer_partial_refund => PUBLIC;
+            accept_counter_offer => PUBLIC;
+            reject_counter_offer => PUBLIC;
             get_payment => PUBLIC;
+            new_host_coupon => PUBLIC;
+            disable_host_coupon => PUBLIC;
+            review_as_owner => PUBLIC;
 
+            get_quote => PUBLIC;
+            get_evidence => PUBLIC;
             new_reservation => PUBLIC;
+            new_reservation_with_swap => PUBLIC;
             reservation_cancellation_by_customer => PUBLIC;
             get_refund => PUBLIC;
             start_dispute => PUBLIC;
+            claim_security_deposit => PUBLIC;
+            submit_evidence_as_customer => PUBLIC;
+            accept_refund_offer => PUBLIC;
+            counter_offer => PUBLIC;
+            reject_refund_offer => PUBLIC;
+            review_as_customer => PUBLIC;
 
             dispute_vote => PUBLIC;
+            commit_vote => PUBLIC;
+            reveal_vote => PUBLIC;
+            declare_conflict => PUBLIC;
+            claim_arbitration_fees => PUBLIC;
+            stake => PUBLIC;
+            request_unstake => PUBLIC;
+            unstake => PUBLIC;
+            replace_absent_panelists => PUBLIC;
         }
     }
 
     struct BookingSystem {
+        owner_badge_address: ResourceAddress,
         last_user_id: u64,
         users_resource_manager: ResourceManager,
+        // Badge ids have their own sequence because a recovery gives a user a new badge
+        last_badge_id: u64,
+        user_badges: KeyValueStore<u64, u64>,
+        registration_mode: RegistrationMode,
+        registered_accounts: KeyValueStore<ComponentAddress, u64>,
+        registered_identities: KeyValueStore<NonFungibleGlobalId, u64>,
 
         last_item_id: u64,
         items: KeyValueStore<u64, Item>,
@@ -61,8 +188,49 @@ mod booking_system {
         last_arbitrator_id: u64,
         arbitrators_resource_manager: ResourceManager,
         min_arbitrators: u16,
+        active_arbitrators: Vec<u64>,
+        arbitration_fees: KeyValueStore<ResourceAddress, Fee>,
+        loser_pays_arbitration: bool,
+        vote_aggregation: VoteAggregation,
+        // Arbitration fees not claimed yet
+        arbitrator_rewards: KeyValueStore<u64, Rewards>,
+        // None when arbitrators don't need to stake
+        staking_settings: Option<StakingSettings>,
+        stakes: KeyValueStore<u64, Stake>,
+        // Slashed stakes, used to reward the consistent voters
+        slashed_stakes: Option<Vault>,
 
         payment_delay: i64,
+
+        swap_components: KeyValueStore<ComponentAddress, ()>,
+
+        platform_fees: KeyValueStore<ResourceAddress, Fee>,
+        treasury: KeyValueStore<ResourceAddress, Vault>,
+
+        coupons: KeyValueStore<String, Coupon>,
+
+        loyalty_resource_manager: ResourceManager,
+        loyalty_earn_rate: Decimal,
+        loyalty_expiry: Option<i64>,
+        loyalty_burn_rates: KeyValueStore<ResourceAddress, Decimal>,
+        loyalty_accounts: KeyValueStore<u64, LoyaltyAccount>,
+
+        referral_share: Decimal,
+        referral_max_reservations: u32,
+        referral_counts: KeyValueStore<u64, u32>,
+        referral_rewards: KeyValueStore<u64, Rewards>,
+
+        guest_lists: KeyValueStore<u64, GuestLists>,
+
+        suspended_users: KeyValueStore<u64, Suspension>,
+
+        // Payments collected by delegates on behalf of item owners
+        owner_payouts: KeyValueStore<u64, Rewards>,
+
+        organisations: KeyValueStore<u64, Organisation>,
+        last_organisation_id: u64,
+
+        dispute_settings: DisputeSettings,
     }
 
     impl BookingSystem {
@@ -96,6 +264,18 @@ mod booking_system {
                 non_fungible_data_updater => rule!(require(global_caller(component_address)));
                 non_fungible_data_updater_updater => rule!(require(owner_badge_address));
             ))
+            .burn_roles(burn_roles!(
+                burner => rule!(require(global_caller(component_address)) || require(owner_badge_address));
+                burner_updater => rule!(require(owner_badge_address));
+            ))
+            .withdraw_roles(withdraw_roles!(
+                withdrawer => rule!(allow_all); // Transferable until set_user_badges_soulbound is called
+                withdrawer_updater => rule!(require(global_caller(component_address)) || require(owner_badge_address));
+            ))
+            .recall_roles(recall_roles!(
+                recaller => rule!(require(global_caller(component_address)) || require(owner_badge_address)); // Lost badges can be recalled
+                recaller_updater => rule!(require(owner_badge_address));
+            ))
             .create_with_no_initial_supply();
 
             let reservations_resource_manager = ResourceBuilder::new_integer_non_fungible::<ReservationNFT>(
@@ -144,6 +324,10 @@ mod booking_system {
                 minter => rule!(require(global_caller(component_address)));
                 minter_updater => rule!(require(owner_badge_address));
             ))
+            .non_fungible_data_update_roles(non_fungible_data_update_roles!(
+                non_fungible_data_updater => rule!(require(global_caller(component_address)));
+                non_fungible_data_updater_updater => rule!(require(owner_badge_address));
+            ))
             .burn_roles(burn_roles!(
                 burner => rule!(require(owner_badge_address));
                 burner_updater => rule!(require(owner_badge_address));
@@ -158,8 +342,38 @@ mod booking_system {
             ))
             .create_with_no_initial_supply();
 
+            let loyalty_resource_manager = ResourceBuilder::new_fungible(
+                OwnerRole::Updatable(rule!(require(owner_badge_address)))
+            )
+            .metadata(metadata!(
+                roles {
+                    metadata_setter => rule!(require(owner_badge_address));
+                    metadata_setter_updater => rule!(require(owner_badge_address));
+                    metadata_locker => rule!(require(owner_badge_address));
+                    metadata_locker_updater => rule!(require(owner_badge_address));
+                },
+                init {
+                    "name" => "Loyalty point", updatable;
+                }
+            ))
+            .mint_roles(mint_roles!(
+                minter => rule!(require(global_caller(component_address)));
+                minter_updater => rule!(require(owner_badge_address));
+            ))
+            .burn_roles(burn_roles!(
+                burner => rule!(require(global_caller(component_address)));
+                burner_updater => rule!(require(owner_badge_address));
+            ))
+            .create_with_no_initial_supply();
+
             Self {
+                owner_badge_address: owner_badge_address,
                 last_user_id: 0,
+                last_badge_id: 0,
+                user_badges: KeyValueStore::new(),
+                registration_mode: RegistrationMode::Open,
+                registered_accounts: KeyValueStore::new(),
+                registered_identities: KeyValueStore::new(),
                 users_resource_manager: users_resource_manager,
                 last_item_id: 0,
                 items: KeyValueStore::new(),
@@ -169,11 +383,40 @@ mod booking_system {
                 payment_delay: 0,
                 last_arbitrator_id: 0,
                 min_arbitrators: 1,
+                active_arbitrators: vec![],
+                arbitration_fees: KeyValueStore::new(),
+                loser_pays_arbitration: false,
+                vote_aggregation: VoteAggregation::Mean,
+                arbitrator_rewards: KeyValueStore::new(),
+                staking_settings: None,
+                stakes: KeyValueStore::new(),
+                slashed_stakes: None,
+                swap_components: KeyValueStore::new(),
+                platform_fees: KeyValueStore::new(),
+                treasury: KeyValueStore::new(),
+                coupons: KeyValueStore::new(),
+                loyalty_resource_manager: loyalty_resource_manager,
+                loyalty_earn_rate: Decimal::ZERO,
+                loyalty_expiry: None,
+                loyalty_burn_rates: KeyValueStore::new(),
+                loyalty_accounts: KeyValueStore::new(),
+                referral_share: Decimal::ZERO,
+                referral_max_reservations: 0,
+                referral_counts: KeyValueStore::new(),
+                referral_rewards: KeyValueStore::new(),
+                guest_lists: KeyValueStore::new(),
+                suspended_users: KeyValueStore::new(),
+                owner_payouts: KeyValueStore::new(),
+                organisations: KeyValueStore::new(),
+                last_organisation_id: 0,
+                dispute_settings: DisputeSettings::none(),
             }
             .instantiate()
             .prepare_to_globalize(OwnerRole::Updatable(rule!(require(owner_badge_address))))
             .roles(roles!(
                 arbitrator => rule!(require(arbitrators_resource_manager.address()));
+                verifier => rule!(require(owner_badge_address));
+                moderator => rule!(require(owner_badge_address));
             ))
             .with_address(address_reservation)
             .globalize()
@@ -181,12 +424,58 @@ mod booking_system {
 
         pub fn new_user(
             &mut self,
+            referrer_id: Option<u64>,
+            account: Option<Global<Account>>,
+            identity_proof: Option<Proof>,
         ) -> Bucket {
+            if referrer_id.is_some() {
+                assert!(
+                    referrer_id.unwrap() > 0 && referrer_id.unwrap() <= self.last_user_id,
+                    "Referrer not found",
+                );
+            }
+
             self.last_user_id += 1;
 
+            match self.registration_mode {
+                RegistrationMode::Open => {},
+
+                RegistrationMode::Account => {
+                    let account = account.expect("Account required");
+                    Runtime::assert_access_rule(account.get_owner_role().rule);
+
+                    let account_address = account.address();
+                    assert!(
+                        self.registered_accounts.get(&account_address).is_none(),
+                        "This account already has a user badge",
+                    );
+                    self.registered_accounts.insert(account_address, self.last_user_id);
+                },
+
+                RegistrationMode::IdentityBadge(identity_badge_address) => {
+                    let checked_proof = identity_proof.expect("Identity badge required").check_with_message(
+                        identity_badge_address,
+                        "Incorrect identity badge proof",
+                    ).as_non_fungible();
+
+                    let identity = NonFungibleGlobalId::new(
+                        identity_badge_address,
+                        checked_proof.non_fungible_local_id(),
+                    );
+                    assert!(
+                        self.registered_identities.get(&identity).is_none(),
+                        "This identity already has a user badge",
+                    );
+                    self.registered_identities.insert(identity, self.last_user_id);
+                },
+            }
+
+            self.last_badge_id += 1;
+            self.user_badges.insert(self.last_user_id, self.last_badge_id);
+
             self.users_resource_manager.mint_non_fungible(
-                &NonFungibleLocalId::integer(self.last_user_id.into()),
-                User::new(self.last_user_id),
+                &NonFungibleLocalId::integer(self.last_badge_id.into()),
+                User::new(self.last_user_id, referrer_id),
             )
         }
 
@@ -199,263 +488,1880 @@ mod booking_system {
                 "Incorrect user proof",
             ).as_non_fungible();
 
-            checked_proof.non_fungible::<User>().data()
+            let non_fungible = checked_proof.non_fungible::<User>();
+            let user = non_fungible.data();
+            assert!(
+                *non_fungible.local_id() == self.user_badge_id(user.id),
+                "Revoked user badge",
+            );
+
+            user
         }
 
-        pub fn new_item(
+        // The id of the user badge currently valid for a user
+        fn user_badge_id(
+            &self,
+            user_id: u64,
+        ) -> NonFungibleLocalId {
+            let badge_id = *self.user_badges.get(&user_id).expect("User not found");
+
+            NonFungibleLocalId::integer(badge_id.into())
+        }
+
+        pub fn set_registration_mode(
             &mut self,
-            user_proof: Proof,
-            minimum_reservation_period: i64,
-            coin: ResourceAddress,
-            min_cancellation_forewarning: i64,
+            registration_mode: RegistrationMode,
         ) {
-            let mut user = self.get_user_data(user_proof);
+            self.registration_mode = registration_mode;
+        }
 
-            self.last_item_id += 1;
-            let item = Item::new(
-                self.last_item_id,
-                user.id,
-                minimum_reservation_period,
-                coin,
-                min_cancellation_forewarning,
-            );
-            self.items.insert(self.last_item_id, item);
+        // Allows an account to register a new user, as an example when the previous one has been
+        // recovered on a different account
+        pub fn unlink_account(
+            &mut self,
+            account_address: ComponentAddress,
+        ) {
+            self.registered_accounts.remove(&account_address).expect("Account not registered");
+        }
 
-            user.owned_items.push(self.last_item_id);
-            self.users_resource_manager.update_non_fungible_data(
-                &NonFungibleLocalId::integer(user.id.into()),
-                "owned_items",
-                user.owned_items,
-            );
+        pub fn unlink_identity(
+            &mut self,
+            identity: NonFungibleGlobalId,
+        ) {
+            self.registered_identities.remove(&identity).expect("Identity not registered");
         }
 
-        pub fn add_or_modify_availability_interval(
+        pub fn set_user_badges_soulbound(
             &mut self,
-            user_proof: Proof,
-            item_id: u64,
-            start_time: i64,
-            available: bool,
-            price_per_minimum_reservation_period: Option<Decimal>,
+            soulbound: bool,
         ) {
-            let user_id = self.get_user_data(user_proof).id;
+            let withdrawer_rule = match soulbound {
+                true => rule!(require(self.owner_badge_address)),
+                false => rule!(allow_all),
+            };
 
-            let mut item = self.items.get_mut(&item_id).expect("Item not found");
+            self.users_resource_manager.set_withdrawable(withdrawer_rule);
+        }
 
-            assert!(
-                item.owner_id == user_id,
-                "You are not the owner of this item",
-            );
+        pub fn set_guardian(
+            &mut self,
+            user_proof: Proof,
+            guardian_id: Option<u64>,
+        ) {
+            let mut user = self.get_user_data(user_proof);
+            if guardian_id.is_some() {
+                assert!(
+                    guardian_id.unwrap() > 0 && guardian_id.unwrap() <= self.last_user_id,
+                    "Guardian not found",
+                );
+            }
 
-            item.add_or_modify_availability_interval(
-                start_time,
-                available,
-                price_per_minimum_reservation_period,
+            user.set_guardian(guardian_id);
+
+            self.users_resource_manager.update_non_fungible_data(
+                &self.user_badge_id(user.id),
+                "guardian_id",
+                user.guardian_id,
             );
         }
 
-        pub fn new_reservation(
+        // Mints a new badge with the same data of the lost one, that is no longer accepted; the
+        // component owner can then recall and burn the old badge
+        // The lost badge is recalled and burned if the vault holding it is known
+        fn replace_user_badge(
             &mut self,
-            user_proof: Proof,
-            item_id: u64,
-            start_time: i64,
-            end_time: i64,
-            bucket: Bucket,
-        ) -> (Bucket, Bucket) {
-            let user = self.get_user_data(user_proof);
+            user_id: u64,
+            lost_badge_vault: Option<InternalAddress>,
+        ) -> Bucket {
+            let old_badge_id = *self.user_badges.get(&user_id).expect("User not found");
+            let old_badge_local_id = NonFungibleLocalId::integer(old_badge_id.into());
+            let user = self.users_resource_manager.get_non_fungible_data::<User>(&old_badge_local_id);
 
-            let mut item = self.items.get_mut(&item_id).expect("Item not found");
+            if let Some(lost_badge_vault) = lost_badge_vault {
+                let lost_badge: Bucket = scrypto_decode(
+                    &ScryptoVmV1Api::object_call_direct(
+                        lost_badge_vault.as_node_id(),
+                        NON_FUNGIBLE_VAULT_RECALL_NON_FUNGIBLES_IDENT,
+                        scrypto_args!(vec![old_badge_local_id]),
+                    )
+                ).unwrap();
+                lost_badge.burn();
+            }
 
-            self.last_reservation_id += 1;
+            self.last_badge_id += 1;
+            self.user_badges.insert(user_id, self.last_badge_id);
 
-            item.new_reservation(
-                self.last_reservation_id,
-                user.id,
-                start_time,
-                end_time,
-                bucket,
-                self.reservations_resource_manager,
+            user.badge_recovery(old_badge_id, self.last_badge_id);
+
+            self.users_resource_manager.mint_non_fungible(
+                &NonFungibleLocalId::integer(self.last_badge_id.into()),
+                user,
             )
         }
 
-        fn burn_reservation_nft(
-            &self,
-            reservation: Bucket,
-        ) -> ReservationNFT {
-            assert!(
-                reservation.resource_address() == self.reservations_resource_manager.address(),
-                "This is not a reservation NFT",
-            );
-            assert!(
-                reservation.amount() == Decimal::ONE,
-                "Cannot process multiple reservations at once",
-            );
-
-            let reservation_data = reservation
-                .as_non_fungible()
-                .non_fungible::<ReservationNFT>()
-                .data();
-
-            reservation.burn();
-
-            reservation_data
+        pub fn recover_user_badge(
+            &mut self,
+            user_id: u64,
+            lost_badge_vault: Option<InternalAddress>,
+        ) -> Bucket {
+            self.replace_user_badge(user_id, lost_badge_vault)
         }
 
-        pub fn reservation_cancellation_by_customer(
+        pub fn recover_user_badge_as_guardian(
             &mut self,
-            reservation: Bucket,
+            user_proof: Proof,
+            user_id: u64,
+            lost_badge_vault: Option<InternalAddress>,
         ) -> Bucket {
-            let reservation_data = self.burn_reservation_nft(reservation);
+            let guardian_id = self.get_user_data(user_proof).id;
+            self.check_not_suspended(guardian_id);
 
+            let user = self.users_resource_manager.get_non_fungible_data::<User>(&self.user_badge_id(user_id));
             assert!(
-                reservation_data.max_cancellation_time >= Clock::current_time_rounded_to_seconds(),
-                "Cannot cancel this reservation now",
+                user.guardian_id == Some(guardian_id),
+                "You are not the guardian of this user",
             );
 
-            self.items.get_mut(&reservation_data.item_id).expect("Item not found").get_reservation(reservation_data.id).cancellation_by_customer()
+            self.replace_user_badge(user_id, lost_badge_vault)
         }
 
-        pub fn reservation_cancellation_by_owner(
+        pub fn update_profile(
             &mut self,
             user_proof: Proof,
-            item_id: u64,
-            reservation_id: u64,
+            display_name: String,
+            avatar_url: String,
+            contact_hash: Option<Hash>,
+            preferred_language: String,
         ) {
-            let user_id = self.get_user_data(user_proof).id;
+            let mut user = self.get_user_data(user_proof);
 
-            let mut item = self.items.get_mut(&item_id).expect("Item not found");
-            assert!(
-                item.owner_id == user_id,
-                "You are not the owner",
+            user.update_profile(
+                display_name,
+                avatar_url,
+                contact_hash,
+                preferred_language,
             );
-            item.get_reservation(reservation_id).cancellation_by_owner();
 
-            self.reservations_resource_manager.update_non_fungible_data(
-                &NonFungibleLocalId::integer(reservation_id.into()),
-                "status",
-                ReservationStatus::OwnerCancelled,
-            );
+            let local_id = self.user_badge_id(user.id);
+            self.users_resource_manager.update_non_fungible_data(&local_id, "display_name", user.display_name);
+            self.users_resource_manager.update_non_fungible_data(&local_id, "avatar_url", user.avatar_url);
+            self.users_resource_manager.update_non_fungible_data(&local_id, "contact_hash", user.contact_hash);
+            self.users_resource_manager.update_non_fungible_data(&local_id, "preferred_language", user.preferred_language);
         }
 
-        pub fn get_refund(
+        pub fn set_user_verified(
             &mut self,
-            reservation: Bucket,
-        ) -> Bucket {
-            let reservation_data = self.burn_reservation_nft(reservation);
+            user_id: u64,
+            verified: bool,
+        ) {
+            let local_id = self.user_badge_id(user_id);
+            let mut user = self.users_resource_manager.get_non_fungible_data::<User>(&local_id);
 
-            self.items.get_mut(&reservation_data.item_id).expect("Item not found").get_reservation(reservation_data.id).get_refund()
+            user.set_verified(verified);
+
+            self.users_resource_manager.update_non_fungible_data(&local_id, "verified", user.verified);
         }
 
-        pub fn start_dispute(
+        fn check_not_suspended(
+            &self,
+            user_id: u64,
+        ) {
+            assert!(
+                self.suspended_users.get(&user_id).is_none(),
+                "User suspended",
+            );
+        }
+
+        pub fn suspend_user(
             &mut self,
-            reservation_proof: Proof,
+            user_id: u64,
+            reason_code: u16,
         ) {
-            let checked_proof = reservation_proof.check_with_message(
-                self.reservations_resource_manager.address(),
-                "Incorrect reservation proof",
-            ).as_non_fungible();
-            let reservation_data = checked_proof.non_fungible::<ReservationNFT>().data();
+            assert!(
+                user_id > 0 && user_id <= self.last_user_id,
+                "User not found",
+            );
 
-            self.items.get_mut(&reservation_data.item_id).expect("Item not found").get_reservation(reservation_data.id).start_dispute();
+            self.suspended_users.insert(user_id, Suspension::new(user_id, reason_code));
+        }
 
-            self.reservations_resource_manager.update_non_fungible_data(
-                &NonFungibleLocalId::integer(reservation_data.id.into()),
-                "status",
-                ReservationStatus::Disputing,
-            );
+        pub fn unsuspend_user(
+            &mut self,
+            user_id: u64,
+        ) {
+            self.suspended_users.remove(&user_id).expect("User not suspended").lift();
         }
 
-        pub fn offer_partial_refund(
+        pub fn delist_item(
             &mut self,
-            user_proof: Proof,
             item_id: u64,
-            reservation_id: u64,
-            refund_amount: Decimal,
+            reason_code: u16,
         ) {
-            let owner_id = self.get_user_data(user_proof).id;
+            let cancelled_reservations = self.items.get_mut(&item_id).expect("Item not found").delist(reason_code);
 
-            let mut item = self.items.get_mut(&item_id).expect("Item not found");
-            assert!(
-                item.owner_id == owner_id,
-                "You are not the owner",
-            );
-            item.get_reservation(reservation_id).offer_partial_refund(refund_amount);
+            for reservation_id in cancelled_reservations {
+                self.release_subsidy(item_id, reservation_id);
+
+                self.reservations_resource_manager.update_non_fungible_data(
+                    &NonFungibleLocalId::integer(reservation_id.into()),
+                    "status",
+                    ReservationStatus::OwnerCancelled,
+                );
+            }
         }
 
-        pub fn set_payment_delay(
+        pub fn relist_item(
             &mut self,
-            payment_delay: i64,
+            item_id: u64,
         ) {
-            assert!(
-                payment_delay >= 0,
-                "Negative payment_delay not allowed",
-            );
+            self.items.get_mut(&item_id).expect("Item not found").relist();
+        }
 
-            self.payment_delay = payment_delay;
+        // A suspended user, or the owner of a delisted item, can ask the component owner to
+        // reconsider the decision
+        pub fn appeal(
+            &mut self,
+            user_proof: Proof,
+            item_id: Option<u64>,
+            content_hash: Hash,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+
+            match item_id {
+                Some(item_id) => {
+                    let item = self.items.get(&item_id).expect("Item not found");
+                    assert!(
+                        item.owner_id == user_id && item.delisted,
+                        "Nothing to appeal",
+                    );
+                },
+                None => assert!(
+                    self.suspended_users.get(&user_id).is_some(),
+                    "Nothing to appeal",
+                ),
+            }
+
+            appeal(user_id, item_id, content_hash);
         }
 
-        pub fn get_arbitrator_badge(
+        pub fn new_organisation(
             &mut self,
-        ) -> Bucket {
-            self.last_arbitrator_id += 1;
+            user_proof: Proof,
+            name: String,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_not_suspended(user_id);
 
-            self.arbitrators_resource_manager.mint_non_fungible(
-                &NonFungibleLocalId::integer(self.last_arbitrator_id.into()),
-                Arbitrator::new(self.last_arbitrator_id),
-            )
+            self.last_organisation_id += 1;
+            self.organisations.insert(
+                self.last_organisation_id,
+                Organisation::new(self.last_organisation_id, name, user_id),
+            );
         }
 
-        pub fn set_min_arbitrators(
+        pub fn set_organisation_member(
             &mut self,
-            min_arbitrators: u16,
+            user_proof: Proof,
+            organisation_id: u64,
+            member_id: u64,
+            role: Option<OrganisationRole>,
         ) {
+            let user_id = self.get_user_data(user_proof).id;
             assert!(
-                min_arbitrators > 0,
-                "At least 1 arbitrator to decide",
+                member_id > 0 && member_id <= self.last_user_id,
+                "User not found",
             );
 
-            self.min_arbitrators = min_arbitrators;
+            self.organisations.get_mut(&organisation_id).expect("Organisation not found").set_member(user_id, member_id, role);
         }
 
-        pub fn get_payment(
+        pub fn withdraw_organisation_treasury(
             &mut self,
             user_proof: Proof,
-            item_id: u64,
-            reservation_id: u64,
-        ) -> Bucket {
-            let owner_id = self.get_user_data(user_proof).id;
+            organisation_id: u64,
+        ) -> Vec<Bucket> {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_not_suspended(user_id);
 
-            let mut item = self.items.get_mut(&item_id).expect("Item not found");
+            let mut organisation = self.organisations.get_mut(&organisation_id).expect("Organisation not found");
             assert!(
-                item.owner_id == owner_id,
-                "You are not the owner",
+                organisation.allows(user_id, None),
+                "You are not an admin of this organisation",
             );
-            let payment = item.get_reservation(reservation_id).get_payment(self.payment_delay);
 
-            payment
+            organisation.treasury.withdraw_all()
         }
 
-        pub fn dispute_vote(
+        pub fn new_item(
             &mut self,
-            arbitrator_proof: Proof,
+            user_proof: Proof,
+            minimum_reservation_period: i64,
+            coin: ResourceAddress,
+            min_cancellation_forewarning: i64,
+            organisation_id: Option<u64>,
+        ) {
+            let mut user = self.get_user_data(user_proof);
+            self.check_not_suspended(user.id);
+
+            if organisation_id.is_some() {
+                assert!(
+                    self.organisations.get(&organisation_id.unwrap()).expect("Organisation not found").allows(user.id, None),
+                    "You are not an admin of this organisation",
+                );
+            }
+
+            self.last_item_id += 1;
+            let item = Item::new(
+                self.last_item_id,
+                user.id,
+                organisation_id,
+                minimum_reservation_period,
+                coin,
+                min_cancellation_forewarning,
+            );
+            self.items.insert(self.last_item_id, item);
+
+            user.owned_items.push(self.last_item_id);
+            self.users_resource_manager.update_non_fungible_data(
+                &self.user_badge_id(user.id),
+                "owned_items",
+                user.owned_items,
+            );
+        }
+
+        pub fn add_or_modify_availability_interval(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            start_time: i64,
+            available: bool,
+            price_per_minimum_reservation_period: Option<Decimal>,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_item_permission(item_id, user_id, Some(DelegatePermission::ManageAvailability));
+
+            let mut item = self.items.get_mut(&item_id).expect("Item not found");
+
+            item.add_or_modify_availability_interval(
+                start_time,
+                available,
+                price_per_minimum_reservation_period,
+            );
+        }
+
+        pub fn set_pricing_rules(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            weekly_discount_percentage: Decimal,
+            monthly_discount_percentage: Decimal,
+            early_bird_advance: i64,
+            early_bird_discount_percentage: Decimal,
+            last_minute_window: i64,
+            last_minute_percentage: Decimal,
+            service_fee: Decimal,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_item_permission(item_id, user_id, Some(DelegatePermission::ManageAvailability));
+
+            let mut item = self.items.get_mut(&item_id).expect("Item not found");
+
+            item.set_pricing_rules(
+                PricingRules::new(
+                    item_id,
+                    weekly_discount_percentage,
+                    monthly_discount_percentage,
+                    early_bird_advance,
+                    early_bird_discount_percentage,
+                    last_minute_window,
+                    last_minute_percentage,
+                    service_fee,
+                )
+            );
+        }
+
+        pub fn set_dynamic_pricing(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            window: i64,
+            min_multiplier: Decimal,
+            max_multiplier: Decimal,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_item_permission(item_id, user_id, Some(DelegatePermission::ManageAvailability));
+
+            let mut item = self.items.get_mut(&item_id).expect("Item not found");
+
+            item.set_dynamic_pricing(
+                DynamicPricing::new(
+                    item_id,
+                    window,
+                    min_multiplier,
+                    max_multiplier,
+                )
+            );
+        }
+
+        pub fn set_verified_guests_only(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            verified_guests_only: bool,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_item_permission(item_id, user_id, None);
+
+            let mut item = self.items.get_mut(&item_id).expect("Item not found");
+
+            item.verified_guests_only = verified_guests_only;
+        }
+
+        pub fn set_item_private(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            private: bool,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_item_permission(item_id, user_id, None);
+
+            let mut item = self.items.get_mut(&item_id).expect("Item not found");
+
+            item.private = private;
+        }
+
+        pub fn set_security_deposit(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            security_deposit: Decimal,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_item_permission(item_id, user_id, None);
+            assert!(
+                security_deposit >= Decimal::ZERO,
+                "Negative security_deposit not allowed",
+            );
+
+            self.items.get_mut(&item_id).expect("Item not found").security_deposit = security_deposit;
+        }
+
+        // The owner, or the admins of the owning organisation, can do anything on an item; delegates
+        // and the other organisation members only what they are allowed to. None for the actions
+        // reserved to the owner
+        fn check_item_permission(
+            &self,
+            item_id: u64,
+            user_id: u64,
+            permission: Option<DelegatePermission>,
+        ) {
+            let item = self.items.get(&item_id).expect("Item not found");
+
+            let owner_allowed = match item.organisation_id {
+                Some(organisation_id) => self.organisations.get(&organisation_id).unwrap().allows(user_id, permission),
+                None => item.owner_id == user_id,
+            };
+            let delegate_allowed = match permission {
+                Some(permission) => item.delegate_allowed(user_id, permission),
+                None => false,
+            };
+
+            assert!(
+                owner_allowed || delegate_allowed,
+                "You are not allowed to do this on this item",
+            );
+        }
+
+        pub fn set_item_delegate(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            delegate_id: u64,
+            permissions: Vec<DelegatePermission>,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            assert!(
+                delegate_id > 0 && delegate_id <= self.last_user_id,
+                "User not found",
+            );
+            self.check_item_permission(item_id, user_id, None);
+
+            let mut item = self.items.get_mut(&item_id).expect("Item not found");
+
+            item.set_delegate(delegate_id, permissions);
+        }
+
+        fn get_guest_lists(
+            &mut self,
+            owner_id: u64,
+        ) -> KeyValueEntryRefMut<'_, GuestLists> {
+            if self.guest_lists.get(&owner_id).is_none() {
+                self.guest_lists.insert(owner_id, GuestLists::new(owner_id));
+            }
+
+            self.guest_lists.get_mut(&owner_id).unwrap()
+        }
+
+        pub fn set_guest_blocked(
+            &mut self,
+            user_proof: Proof,
+            guest_id: u64,
+            blocked: bool,
+        ) {
+            let owner_id = self.get_user_data(user_proof).id;
+
+            self.get_guest_lists(owner_id).set_blocked(guest_id, blocked);
+        }
+
+        pub fn set_guest_allowed(
+            &mut self,
+            user_proof: Proof,
+            guest_id: u64,
+            allowed: bool,
+        ) {
+            let owner_id = self.get_user_data(user_proof).id;
+
+            self.get_guest_lists(owner_id).set_allowed(guest_id, allowed);
+        }
+
+        pub fn get_quote(
+            &self,
+            item_id: u64,
+            start_time: i64,
+            end_time: i64,
+        ) -> PriceQuote {
+            self.items.get(&item_id).expect("Item not found").quote(start_time, end_time)
+        }
+
+        pub fn new_reservation(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            start_time: i64,
+            end_time: i64,
+            bucket: Bucket,
+            coupon_code: Option<String>,
+            loyalty_points: Option<Decimal>,
+        ) -> (Bucket, Bucket) {
+            let user = self.get_user_data(user_proof);
+            self.check_not_suspended(user.id);
+
+            {
+                let item = self.items.get(&item_id).expect("Item not found");
+
+                assert!(
+                    self.suspended_users.get(&item.owner_id).is_none(),
+                    "Item owner suspended",
+                );
+                assert!(
+                    user.verified || !item.verified_guests_only,
+                    "Only verified users can book this item",
+                );
+
+                match self.guest_lists.get(&item.owner_id) {
+                    Some(guest_lists) => guest_lists.check_guest(user.id, item.private),
+                    None => assert!(
+                        !item.private,
+                        "This item is private",
+                    ),
+                }
+            }
+
+            let loyalty_discount = match loyalty_points {
+                Some(loyalty_points) => {
+                    assert!(
+                        loyalty_points > Decimal::ZERO,
+                        "loyalty_points must be positive",
+                    );
+                    let loyalty_burn_rate = *self.loyalty_burn_rates.get(&bucket.resource_address()).expect("Loyalty points can't be used with this coin");
+                    self.loyalty_accounts.get_mut(&user.id).expect("No loyalty points").redeem(loyalty_points);
+
+                    loyalty_points * loyalty_burn_rate
+                },
+                None => Decimal::ZERO,
+            };
+
+            let mut coupon = match coupon_code.clone() {
+                Some(code) => Some(self.coupons.get_mut(&code).expect("Coupon not found")),
+                None => None,
+            };
+
+            let mut item = self.items.get_mut(&item_id).expect("Item not found");
+            let mut treasury = self.treasury.get_mut(&item.coin);
+
+            self.last_reservation_id += 1;
+
+            item.new_reservation(
+                self.last_reservation_id,
+                user.id,
+                start_time,
+                end_time,
+                bucket,
+                self.reservations_resource_manager,
+                coupon_code,
+                coupon.as_deref_mut(),
+                loyalty_discount,
+                treasury.as_deref_mut(),
+            )
+        }
+
+        pub fn new_reservation_with_swap(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            start_time: i64,
+            end_time: i64,
+            bucket: Bucket,
+            swap_component_address: ComponentAddress,
+            min_output: Decimal,
+            coupon_code: Option<String>,
+            loyalty_points: Option<Decimal>,
+        ) -> (Bucket, Bucket) {
+            let coin = self.items.get(&item_id).expect("Item not found").coin;
+
+            let bucket = match bucket.resource_address() == coin {
+                true => bucket,
+                false => {
+                    assert!(
+                        self.swap_components.get(&swap_component_address).is_some(),
+                        "Swap component not allowed",
+                    );
+
+                    let swap_component: Global<AnyComponent> = Global::from(swap_component_address);
+                    let coins: Bucket = swap_component.call_raw("swap", scrypto_args!(bucket));
+
+                    assert!(
+                        coins.resource_address() == coin,
+                        "Swap returned the wrong coin",
+                    );
+                    assert!(
+                        coins.amount() >= min_output,
+                        "Swap output below minimum",
+                    );
+
+                    coins
+                }
+            };
+
+            self.new_reservation(
+                user_proof,
+                item_id,
+                start_time,
+                end_time,
+                bucket,
+                coupon_code,
+                loyalty_points,
+            )
+        }
+
+        fn burn_reservation_nft(
+            &self,
+            reservation: Bucket,
+        ) -> ReservationNFT {
+            assert!(
+                reservation.resource_address() == self.reservations_resource_manager.address(),
+                "This is not a reservation NFT",
+            );
+            assert!(
+                reservation.amount() == Decimal::ONE,
+                "Cannot process multiple reservations at once",
+            );
+
+            let reservation_data = reservation
+                .as_non_fungible()
+                .non_fungible::<ReservationNFT>()
+                .data();
+
+            reservation.burn();
+
+            reservation_data
+        }
+
+        pub fn reservation_cancellation_by_customer(
+            &mut self,
+            reservation: Bucket,
+        ) -> Bucket {
+            let reservation_data = self.burn_reservation_nft(reservation);
+
+            assert!(
+                reservation_data.max_cancellation_time >= Clock::current_time_rounded_to_seconds(),
+                "Cannot cancel this reservation now",
+            );
+
+            let refund = self.items.get_mut(&reservation_data.item_id).expect("Item not found").get_reservation(reservation_data.id).cancellation_by_customer();
+            self.release_subsidy(reservation_data.item_id, reservation_data.id);
+
+            refund
+        }
+
+        pub fn reservation_cancellation_by_owner(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            reservation_id: u64,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_item_permission(item_id, user_id, Some(DelegatePermission::CancelReservations));
+
+            self.apply_dispute_deadlines(item_id, reservation_id);
+
+            self.items.get_mut(&item_id).expect("Item not found").get_reservation(reservation_id).cancellation_by_owner();
+            self.release_subsidy(item_id, reservation_id);
+
+            self.reservations_resource_manager.update_non_fungible_data(
+                &NonFungibleLocalId::integer(reservation_id.into()),
+                "status",
+                ReservationStatus::OwnerCancelled,
+            );
+        }
+
+        pub fn get_refund(
+            &mut self,
+            reservation: Bucket,
+        ) -> Bucket {
+            let reservation_data = self.burn_reservation_nft(reservation);
+            self.apply_dispute_deadlines(reservation_data.item_id, reservation_data.id);
+
+            self.items.get_mut(&reservation_data.item_id).expect("Item not found").get_reservation(reservation_data.id).get_refund()
+        }
+
+        pub fn start_dispute(
+            &mut self,
+            reservation_proof: Proof,
+        ) {
+            let reservation_data = self.get_reservation_data(reservation_proof);
+
+            self.items.get_mut(&reservation_data.item_id).expect("Item not found").get_reservation(reservation_data.id).start_dispute(self.dispute_settings.dispute_window);
+            self.update_panel(reservation_data.item_id, reservation_data.id);
+
+            self.reservations_resource_manager.update_non_fungible_data(
+                &NonFungibleLocalId::integer(reservation_data.id.into()),
+                "status",
+                ReservationStatus::Disputing,
+            );
+        }
+
+        pub fn start_dispute_as_owner(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            reservation_id: u64,
+            claim_amount: Decimal,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_item_permission(item_id, user_id, None);
+
+            self.items.get_mut(&item_id).expect("Item not found").get_reservation(reservation_id).start_dispute_as_owner(claim_amount, self.dispute_settings.dispute_window);
+            self.update_panel(item_id, reservation_id);
+
+            self.reservations_resource_manager.update_non_fungible_data(
+                &NonFungibleLocalId::integer(reservation_id.into()),
+                "status",
+                ReservationStatus::Disputing,
+            );
+        }
+
+        // The security deposit is held until the owner can no longer open a dispute
+        pub fn claim_security_deposit(
+            &mut self,
+            reservation_proof: Proof,
+        ) -> Bucket {
+            let reservation_data = self.get_reservation_data(reservation_proof);
+
+            let hold_period = cmp::max(self.payment_delay, self.dispute_settings.dispute_window.unwrap_or(0));
+
+            self.items.get_mut(&reservation_data.item_id).expect("Item not found").get_reservation(reservation_data.id).claim_security_deposit(hold_period)
+        }
+
+        pub fn offer_partial_refund(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            reservation_id: u64,
+            refund_amount: Decimal,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_item_permission(item_id, user_id, Some(DelegatePermission::OfferRefunds));
+
+            self.apply_dispute_deadlines(item_id, reservation_id);
+
+            let mut item = self.items.get_mut(&item_id).expect("Item not found");
+            item.get_reservation(reservation_id).offer_partial_refund(refund_amount);
+        }
+
+        // Dispute deadlines are applied by the first call after they expire
+        fn apply_dispute_deadlines(
+            &mut self,
+            item_id: u64,
+            reservation_id: u64,
+        ) {
+            let (new_status, absent) = {
+                let mut item = self.items.get_mut(&item_id).expect("Item not found");
+                let mut reservation = item.get_reservation(reservation_id);
+
+                let (status_changed, absent) = reservation.apply_dispute_deadlines(&self.dispute_settings);
+                match status_changed {
+                    true => (Some(reservation.status), absent),
+                    false => (None, absent),
+                }
+            };
+
+            if new_status.is_some() {
+                self.reservations_resource_manager.update_non_fungible_data(
+                    &NonFungibleLocalId::integer(reservation_id.into()),
+                    "status",
+                    new_status.unwrap(),
+                );
+            }
+
+            // The panelists who missed the arbitration deadline are slashed
+            for arbitrator_id in absent {
+                self.slash(arbitrator_id, reservation_id);
+            }
+        }
+
+        pub fn set_dispute_settings(
+            &mut self,
+            dispute_window: Option<i64>,
+            owner_response_window: Option<i64>,
+            evidence_window: Option<i64>,
+            arbitration_window: Option<i64>,
+            vote_window: Option<i64>,
+            commit_window: Option<i64>,
+            default_outcome: DefaultOutcome,
+        ) {
+            self.dispute_settings = DisputeSettings::new(
+                dispute_window,
+                owner_response_window,
+                evidence_window,
+                arbitration_window,
+                vote_window,
+                commit_window,
+                default_outcome,
+            );
+        }
+
+        pub fn submit_evidence_as_customer(
+            &mut self,
+            reservation_proof: Proof,
+            content_hash: Hash,
+            uri: String,
+        ) {
+            let reservation_data = self.get_reservation_data(reservation_proof);
+            self.apply_dispute_deadlines(reservation_data.item_id, reservation_data.id);
+
+            self.items.get_mut(&reservation_data.item_id).expect("Item not found").get_reservation(reservation_data.id).submit_evidence(
+                Party::Customer,
+                content_hash,
+                uri,
+                self.dispute_settings.evidence_window,
+            );
+        }
+
+        pub fn submit_evidence_as_owner(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            reservation_id: u64,
+            content_hash: Hash,
+            uri: String,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_item_permission(item_id, user_id, Some(DelegatePermission::OfferRefunds));
+
+            self.apply_dispute_deadlines(item_id, reservation_id);
+
+            self.items.get_mut(&item_id).expect("Item not found").get_reservation(reservation_id).submit_evidence(
+                Party::Owner,
+                content_hash,
+                uri,
+                self.dispute_settings.evidence_window,
+            );
+        }
+
+        pub fn get_evidence(
+            &self,
+            item_id: u64,
+            reservation_id: u64,
+        ) -> Vec<Evidence> {
+            self.items.get(&item_id).expect("Item not found").get_reservation_ref(reservation_id).get_evidence()
+        }
+
+        fn get_reservation_data(
+            &self,
+            reservation_proof: Proof,
+        ) -> ReservationNFT {
+            let checked_proof = reservation_proof.check_with_message(
+                self.reservations_resource_manager.address(),
+                "Incorrect reservation proof",
+            ).as_non_fungible();
+
+            checked_proof.non_fungible::<ReservationNFT>().data()
+        }
+
+        pub fn accept_refund_offer(
+            &mut self,
+            reservation_proof: Proof,
+            refund_amount: Decimal,
+        ) {
+            let reservation_data = self.get_reservation_data(reservation_proof);
+            self.apply_dispute_deadlines(reservation_data.item_id, reservation_data.id);
+
+            self.items.get_mut(&reservation_data.item_id).expect("Item not found").get_reservation(reservation_data.id).accept_offer(Party::Customer, refund_amount);
+
+            self.reservations_resource_manager.update_non_fungible_data(
+                &NonFungibleLocalId::integer(reservation_data.id.into()),
+                "status",
+                ReservationStatus::Settled,
+            );
+        }
+
+        pub fn counter_offer(
+            &mut self,
+            reservation_proof: Proof,
+            refund_amount: Decimal,
+        ) {
+            let reservation_data = self.get_reservation_data(reservation_proof);
+            self.apply_dispute_deadlines(reservation_data.item_id, reservation_data.id);
+
+            self.items.get_mut(&reservation_data.item_id).expect("Item not found").get_reservation(reservation_data.id).counter_offer(refund_amount);
+        }
+
+        pub fn reject_refund_offer(
+            &mut self,
+            reservation_proof: Proof,
+        ) {
+            let reservation_data = self.get_reservation_data(reservation_proof);
+            self.apply_dispute_deadlines(reservation_data.item_id, reservation_data.id);
+
+            self.items.get_mut(&reservation_data.item_id).expect("Item not found").get_reservation(reservation_data.id).escalate_to_arbitration(Some(Party::Customer));
+
+            self.reservations_resource_manager.update_non_fungible_data(
+                &NonFungibleLocalId::integer(reservation_data.id.into()),
+                "status",
+                ReservationStatus::Arbitration,
+            );
+        }
+
+        pub fn accept_counter_offer(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            reservation_id: u64,
+            refund_amount: Decimal,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_item_permission(item_id, user_id, Some(DelegatePermission::OfferRefunds));
+
+            self.apply_dispute_deadlines(item_id, reservation_id);
+
+            self.items.get_mut(&item_id).expect("Item not found").get_reservation(reservation_id).accept_offer(Party::Owner, refund_amount);
+
+            self.reservations_resource_manager.update_non_fungible_data(
+                &NonFungibleLocalId::integer(reservation_id.into()),
+                "status",
+                ReservationStatus::Settled,
+            );
+        }
+
+        pub fn reject_counter_offer(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            reservation_id: u64,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_item_permission(item_id, user_id, Some(DelegatePermission::OfferRefunds));
+
+            self.apply_dispute_deadlines(item_id, reservation_id);
+
+            self.items.get_mut(&item_id).expect("Item not found").get_reservation(reservation_id).escalate_to_arbitration(Some(Party::Owner));
+
+            self.reservations_resource_manager.update_non_fungible_data(
+                &NonFungibleLocalId::integer(reservation_id.into()),
+                "status",
+                ReservationStatus::Arbitration,
+            );
+        }
+
+        // The customer uses the user badge because the reservation NFT is burned on refunds
+        pub fn review_as_customer(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            reservation_id: u64,
+            rating: u8,
+            content_hash: Hash,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+
+            let owner_id = {
+                let mut item = self.items.get_mut(&item_id).expect("Item not found");
+                let owner_id = item.owner_id;
+
+                let mut reservation = item.get_reservation(reservation_id);
+                assert!(
+                    reservation.customer_id == user_id,
+                    "You are not the customer of this reservation",
+                );
+                reservation.review(
+                    item_id,
+                    owner_id,
+                    Party::Customer,
+                    rating,
+                    content_hash,
+                );
+                drop(reservation);
+                item.rating.add(rating);
+
+                owner_id
+            };
+
+            self.add_user_rating(owner_id, Party::Customer, rating);
+        }
+
+        pub fn review_as_owner(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            reservation_id: u64,
+            rating: u8,
+            content_hash: Hash,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_item_permission(item_id, user_id, None);
+
+            let customer_id = {
+                let mut item = self.items.get_mut(&item_id).expect("Item not found");
+                let owner_id = item.owner_id;
+
+                let mut reservation = item.get_reservation(reservation_id);
+                reservation.review(
+                    item_id,
+                    owner_id,
+                    Party::Owner,
+                    rating,
+                    content_hash,
+                );
+
+                reservation.customer_id
+            };
+
+            self.add_user_rating(customer_id, Party::Owner, rating);
+        }
+
+        // Owners are rated by customers as hosts, customers are rated by owners as guests
+        fn add_user_rating(
+            &self,
+            user_id: u64,
+            reviewer: Party,
+            rating: u8,
+        ) {
+            let local_id = self.user_badge_id(user_id);
+            let user = self.users_resource_manager.get_non_fungible_data::<User>(&local_id);
+
+            let (field_name, mut user_rating) = match reviewer {
+                Party::Customer => ("host_rating", user.host_rating),
+                Party::Owner => ("guest_rating", user.guest_rating),
+            };
+            user_rating.add(rating);
+
+            self.users_resource_manager.update_non_fungible_data(
+                &local_id,
+                field_name,
+                user_rating,
+            );
+        }
+
+        pub fn set_payment_delay(
+            &mut self,
+            payment_delay: i64,
+        ) {
+            assert!(
+                payment_delay >= 0,
+                "Negative payment_delay not allowed",
+            );
+
+            self.payment_delay = payment_delay;
+        }
+
+        pub fn get_arbitrator_badge(
+            &mut self,
+            user_id: u64,
+        ) -> Bucket {
+            assert!(
+                user_id > 0 && user_id <= self.last_user_id,
+                "User not found",
+            );
+
+            self.last_arbitrator_id += 1;
+            self.active_arbitrators.push(self.last_arbitrator_id);
+
+            self.arbitrators_resource_manager.mint_non_fungible(
+                &NonFungibleLocalId::integer(self.last_arbitrator_id.into()),
+                Arbitrator::new(self.last_arbitrator_id, user_id),
+            )
+        }
+
+        // Only active arbitrators are assigned to new panels
+        pub fn set_arbitrator_active(
+            &mut self,
+            arbitrator_id: u64,
+            active: bool,
+        ) {
+            assert!(
+                arbitrator_id > 0 && arbitrator_id <= self.last_arbitrator_id,
+                "Arbitrator not found",
+            );
+
+            self.active_arbitrators.retain(|id| *id != arbitrator_id);
+            if active {
+                self.active_arbitrators.push(arbitrator_id);
+            }
+        }
+
+        fn get_arbitrator_data(
+            &self,
+            arbitrator_proof: Proof,
+        ) -> Arbitrator {
+            let checked_proof = arbitrator_proof.check_with_message(
+                self.arbitrators_resource_manager.address(),
+                "Incorrect arbitrator proof",
+            ).as_non_fungible();
+
+            checked_proof.non_fungible::<Arbitrator>().data()
+        }
+
+        // Arbitrators declare the users they know so that they are not assigned to their disputes
+        pub fn declare_conflict(
+            &mut self,
+            arbitrator_proof: Proof,
+            user_id: u64,
+        ) {
+            let mut arbitrator = self.get_arbitrator_data(arbitrator_proof);
+
+            arbitrator.declare_conflict(user_id);
+
+            self.arbitrators_resource_manager.update_non_fungible_data(
+                &NonFungibleLocalId::integer(arbitrator.id.into()),
+                "conflicts",
+                arbitrator.conflicts,
+            );
+        }
+
+        // Returns the customer and the owner of a reservation
+        fn get_parties(
+            &self,
+            item_id: u64,
+            reservation_id: u64,
+        ) -> (u64, u64) {
+            let item = self.items.get(&item_id).expect("Item not found");
+
+            (item.get_reservation_ref(reservation_id).customer_id, item.owner_id)
+        }
+
+        fn update_panel(
+            &mut self,
+            item_id: u64,
+            reservation_id: u64,
+        ) {
+            let (customer_id, owner_id) = self.get_parties(item_id, reservation_id);
+
+            let candidates: Vec<u64> = self.active_arbitrators.iter()
+                .filter(|arbitrator_id| {
+                    self.is_staked(**arbitrator_id) &&
+                    !self.arbitrators_resource_manager.get_non_fungible_data::<Arbitrator>(
+                        &NonFungibleLocalId::integer((**arbitrator_id).into())
+                    ).is_conflicted(customer_id, owner_id)
+                })
+                .cloned()
+                .collect();
+
+            let absent = self.items.get_mut(&item_id).expect("Item not found").get_reservation(reservation_id).update_panel(
+                candidates,
+                self.min_arbitrators.into(),
+                self.dispute_settings.vote_window,
+                self.dispute_settings.commit_window,
+            );
+
+            for arbitrator_id in absent {
+                self.slash(arbitrator_id, reservation_id);
+            }
+        }
+
+        pub fn set_staking_settings(
+            &mut self,
+            token: ResourceAddress,
+            min_stake: Decimal,
+            slash_percentage: Decimal,
+            vote_tolerance: Decimal,
+            consistency_reward: Decimal,
+            unstake_cooldown: i64,
+        ) {
+            assert!(
+                self.staking_settings.as_ref().map_or(true, |settings| settings.token == token),
+                "The staking token can't be changed",
+            );
+
+            if self.slashed_stakes.is_none() {
+                self.slashed_stakes = Some(Vault::new(token));
+            }
+
+            self.staking_settings = Some(
+                StakingSettings::new(
+                    token,
+                    min_stake,
+                    slash_percentage,
+                    vote_tolerance,
+                    consistency_reward,
+                    unstake_cooldown,
+                )
+            );
+        }
+
+        // Without staking settings every arbitrator is considered staked
+        fn is_staked(
+            &self,
+            arbitrator_id: u64,
+        ) -> bool {
+            match &self.staking_settings {
+                None => true,
+                Some(settings) => self.stakes.get(&arbitrator_id)
+                    .map_or(false, |stake| stake.active_amount() >= settings.min_stake),
+            }
+        }
+
+        fn get_stake(
+            &mut self,
+            arbitrator_id: u64,
+        ) -> KeyValueEntryRefMut<'_, Stake> {
+            let token = self.staking_settings.as_ref().expect("Staking not enabled").token;
+
+            if self.stakes.get(&arbitrator_id).is_none() {
+                self.stakes.insert(arbitrator_id, Stake::new(arbitrator_id, token));
+            }
+
+            self.stakes.get_mut(&arbitrator_id).unwrap()
+        }
+
+        pub fn stake(
+            &mut self,
+            arbitrator_proof: Proof,
+            bucket: Bucket,
+        ) {
+            let arbitrator_id = self.get_arbitrator_data(arbitrator_proof).id;
+
+            self.get_stake(arbitrator_id).deposit(bucket);
+        }
+
+        pub fn request_unstake(
+            &mut self,
+            arbitrator_proof: Proof,
+            amount: Decimal,
+        ) {
+            let arbitrator_id = self.get_arbitrator_data(arbitrator_proof).id;
+
+            self.get_stake(arbitrator_id).request_unstake(amount);
+        }
+
+        pub fn unstake(
+            &mut self,
+            arbitrator_proof: Proof,
+        ) -> Bucket {
+            let arbitrator_id = self.get_arbitrator_data(arbitrator_proof).id;
+            let unstake_cooldown = self.staking_settings.as_ref().expect("Staking not enabled").unstake_cooldown;
+
+            self.get_stake(arbitrator_id).unstake(unstake_cooldown)
+        }
+
+        fn slash(
+            &mut self,
+            arbitrator_id: u64,
+            reservation_id: u64,
+        ) {
+            let slash_percentage = match &self.staking_settings {
+                Some(settings) => settings.slash_percentage,
+                None => return,
+            };
+            if self.stakes.get(&arbitrator_id).is_none() {
+                return;
+            }
+
+            let slashed = self.get_stake(arbitrator_id).slash(reservation_id, slash_percentage);
+            self.slashed_stakes.as_mut().unwrap().put(slashed);
+        }
+
+        // Slashes the arbitrators who voted far from the outcome, then rewards the others with
+        // the slashed stakes
+        fn settle_stakes(
+            &mut self,
+            reservation_id: u64,
+            outcome: Decimal,
+            votes: BTreeMap<u64, Decimal>,
+        ) {
+            let (vote_tolerance, consistency_reward) = match &self.staking_settings {
+                Some(settings) => (settings.vote_tolerance, settings.consistency_reward),
+                None => return,
+            };
+
+            let (consistent, inconsistent): (Vec<(u64, Decimal)>, Vec<(u64, Decimal)>) = votes.into_iter()
+                .partition(|(_, vote)| (*vote - outcome).checked_abs().unwrap() <= vote_tolerance);
+
+            for (arbitrator_id, _) in inconsistent {
+                self.slash(arbitrator_id, reservation_id);
+            }
+
+            for (arbitrator_id, _) in consistent {
+                let slashed_stakes = self.slashed_stakes.as_mut().unwrap();
+                let reward_amount = cmp::min(consistency_reward, slashed_stakes.amount());
+                if reward_amount == Decimal::ZERO {
+                    break;
+                }
+
+                let reward = slashed_stakes.take(reward_amount);
+                self.get_stake(arbitrator_id).deposit(reward);
+            }
+        }
+
+        // Anyone can replace the panelists who didn't vote in time
+        pub fn replace_absent_panelists(
+            &mut self,
+            item_id: u64,
+            reservation_id: u64,
+        ) {
+            self.apply_dispute_deadlines(item_id, reservation_id);
+
+            self.update_panel(item_id, reservation_id);
+        }
+
+        pub fn set_min_arbitrators(
+            &mut self,
+            min_arbitrators: u16,
+        ) {
+            assert!(
+                min_arbitrators > 0,
+                "At least 1 arbitrator to decide",
+            );
+
+            self.min_arbitrators = min_arbitrators;
+        }
+
+        pub fn set_arbitration_fee(
+            &mut self,
+            coin: ResourceAddress,
+            percentage: Decimal,
+            flat: Decimal,
+        ) {
+            self.arbitration_fees.insert(coin, Fee::new(percentage, flat));
+        }
+
+        pub fn set_loser_pays_arbitration(
+            &mut self,
+            loser_pays_arbitration: bool,
+        ) {
+            self.loser_pays_arbitration = loser_pays_arbitration;
+        }
+
+        pub fn set_vote_aggregation(
+            &mut self,
+            vote_aggregation: VoteAggregation,
+        ) {
+            vote_aggregation.check();
+
+            self.vote_aggregation = vote_aggregation;
+        }
+
+        pub fn claim_arbitration_fees(
+            &mut self,
+            arbitrator_proof: Proof,
+        ) -> Vec<Bucket> {
+            let arbitrator_id = self.get_arbitrator_data(arbitrator_proof).id;
+
+            self.arbitrator_rewards.get_mut(&arbitrator_id).expect("No arbitration fees").withdraw_all()
+        }
+
+        pub fn add_swap_component(
+            &mut self,
+            swap_component_address: ComponentAddress,
+        ) {
+            self.swap_components.insert(swap_component_address, ());
+        }
+
+        pub fn remove_swap_component(
+            &mut self,
+            swap_component_address: ComponentAddress,
+        ) {
+            self.swap_components.remove(&swap_component_address);
+        }
+
+        pub fn new_coupon(
+            &mut self,
+            code: String,
+            percentage: Decimal,
+            flat: Decimal,
+            valid_from: i64,
+            valid_until: i64,
+            max_uses: Option<u32>,
+            max_uses_per_user: Option<u32>,
+            item_ids: Vec<u64>,
+            host_ids: Vec<u64>,
+        ) {
+            assert!(
+                self.coupons.get(&code).is_none(),
+                "Coupon code already in use",
+            );
+
+            let coupon = Coupon::new(
+                code.clone(),
+                None,
+                percentage,
+                flat,
+                valid_from,
+                valid_until,
+                max_uses,
+                max_uses_per_user,
+                item_ids,
+                host_ids,
+            );
+            self.coupons.insert(code, coupon);
+        }
+
+        pub fn disable_coupon(
+            &mut self,
+            code: String,
+        ) {
+            self.coupons.get_mut(&code).expect("Coupon not found").disable();
+        }
+
+        pub fn new_host_coupon(
+            &mut self,
+            user_proof: Proof,
+            code: String,
+            percentage: Decimal,
+            flat: Decimal,
+            valid_from: i64,
+            valid_until: i64,
+            max_uses: Option<u32>,
+            max_uses_per_user: Option<u32>,
+            item_ids: Vec<u64>,
+        ) {
+            let user = self.get_user_data(user_proof);
+
+            for item_id in item_ids.iter() {
+                assert!(
+                    user.owned_items.contains(item_id),
+                    "You are not the owner of item {}",
+                    item_id,
+                );
+            }
+
+            assert!(
+                self.coupons.get(&code).is_none(),
+                "Coupon code already in use",
+            );
+
+            let coupon = Coupon::new(
+                code.clone(),
+                Some(user.id),
+                percentage,
+                flat,
+                valid_from,
+                valid_until,
+                max_uses,
+                max_uses_per_user,
+                item_ids,
+                vec![user.id],
+            );
+            self.coupons.insert(code, coupon);
+        }
+
+        pub fn disable_host_coupon(
+            &mut self,
+            user_proof: Proof,
+            code: String,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+
+            let mut coupon = self.coupons.get_mut(&code).expect("Coupon not found");
+            assert!(
+                coupon.creator_id == Some(user_id),
+                "You are not the creator of this coupon",
+            );
+            coupon.disable();
+        }
+
+        pub fn get_payment(
+            &mut self,
+            user_proof: Proof,
+            item_id: u64,
+            reservation_id: u64,
+        ) -> Option<Bucket> {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_not_suspended(user_id);
+            self.check_item_permission(item_id, user_id, Some(DelegatePermission::CollectPayments));
+
+            self.apply_dispute_deadlines(item_id, reservation_id);
+
+            let (payment, fee, subsidy, owner_id, organisation_id, customer_id, completed) = {
+                let mut item = self.items.get_mut(&item_id).expect("Item not found");
+                let owner_id = item.owner_id;
+                let organisation_id = item.organisation_id;
+                let platform_fee = self.platform_fees.get(&item.coin).map(|fee| *fee);
+
+                let mut reservation = item.get_reservation(reservation_id);
+                let (payment, fee, subsidy) = reservation.get_payment(self.payment_delay, platform_fee);
+
+                (payment, fee, subsidy, owner_id, organisation_id, reservation.customer_id, reservation.status == ReservationStatus::Completed)
+            };
+
+            let fee = match completed {
+                true => self.pay_referral_reward(customer_id, fee),
+                false => fee,
+            };
+            self.deposit_to_treasury(fee);
+            self.deposit_to_treasury(subsidy);
+
+            if completed && self.loyalty_earn_rate > Decimal::ZERO {
+                self.credit_loyalty_points(customer_id);
+            }
+
+            if organisation_id.is_some() {
+                self.organisations.get_mut(&organisation_id.unwrap()).unwrap().treasury.deposit(payment);
+
+                return None;
+            }
+
+            // A delegate never gets the payment, it goes to the owner payouts
+            if user_id != owner_id {
+                if self.owner_payouts.get(&owner_id).is_none() {
+                    self.owner_payouts.insert(owner_id, Rewards::new());
+                }
+                self.owner_payouts.get_mut(&owner_id).unwrap().deposit(payment);
+
+                return None;
+            }
+
+            Some(payment)
+        }
+
+        pub fn claim_owner_payouts(
+            &mut self,
+            user_proof: Proof,
+        ) -> Vec<Bucket> {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_not_suspended(user_id);
+
+            self.owner_payouts.get_mut(&user_id).expect("No payouts").withdraw_all()
+        }
+
+        // Moves the referrer share of the platform fee for the first completed reservations of a
+        // user to the referrer and returns what's left of the fee
+        fn pay_referral_reward(
+            &mut self,
+            user_id: u64,
+            mut fee: Bucket,
+        ) -> Bucket {
+            if self.referral_share == Decimal::ZERO || fee.is_empty() {
+                return fee;
+            }
+
+            let user = self.users_resource_manager.get_non_fungible_data::<User>(
+                &self.user_badge_id(user_id)
+            );
+            if user.referrer_id.is_none() {
+                return fee;
+            }
+
+            let referral_count = self.referral_counts.get(&user_id).map(|count| *count).unwrap_or(0);
+            if referral_count >= self.referral_max_reservations {
+                return fee;
+            }
+            self.referral_counts.insert(user_id, referral_count + 1);
+
+            let reward = fee.take_advanced(
+                fee.amount() * self.referral_share / dec![100],
+                WithdrawStrategy::Rounded(RoundingMode::ToZero),
+            );
+            user.referral_reward(reward.resource_address(), reward.amount());
+
+            let referrer_id = user.referrer_id.unwrap();
+            if self.referral_rewards.get(&referrer_id).is_none() {
+                self.referral_rewards.insert(referrer_id, Rewards::new());
+            }
+            self.referral_rewards.get_mut(&referrer_id).unwrap().deposit(reward);
+
+            fee
+        }
+
+        pub fn claim_referral_rewards(
+            &mut self,
+            user_proof: Proof,
+        ) -> Vec<Bucket> {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_not_suspended(user_id);
+
+            self.referral_rewards.get_mut(&user_id).expect("No referral rewards").withdraw_all()
+        }
+
+        pub fn set_referral_program(
+            &mut self,
+            referral_share: Decimal,
+            referral_max_reservations: u32,
+        ) {
+            assert!(
+                referral_share >= Decimal::ZERO && referral_share <= dec![100],
+                "referral_share out of 0-100 range",
+            );
+
+            self.referral_share = referral_share;
+            self.referral_max_reservations = referral_max_reservations;
+        }
+
+        fn credit_loyalty_points(
+            &mut self,
+            user_id: u64,
+        ) {
+            let expiry_time = self.loyalty_expiry.map(|loyalty_expiry|
+                Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch + loyalty_expiry
+            );
+
+            if self.loyalty_accounts.get(&user_id).is_none() {
+                self.loyalty_accounts.insert(user_id, LoyaltyAccount::new(user_id));
+            }
+
+            self.loyalty_accounts.get_mut(&user_id).unwrap().credit(self.loyalty_earn_rate, expiry_time);
+        }
+
+        pub fn claim_loyalty_points(
+            &mut self,
+            user_proof: Proof,
+        ) -> Bucket {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_not_suspended(user_id);
+
+            let amount = self.loyalty_accounts.get_mut(&user_id).expect("No loyalty points").claim();
+
+            self.loyalty_resource_manager.mint(amount)
+        }
+
+        // Burns claimed loyalty points and credits them back to the user, without expiry, so that
+        // they can be redeemed in new_reservation
+        pub fn deposit_loyalty_points(
+            &mut self,
+            user_proof: Proof,
+            loyalty_points: Bucket,
+        ) {
+            let user_id = self.get_user_data(user_proof).id;
+            self.check_not_suspended(user_id);
+
+            assert!(
+                loyalty_points.resource_address() == self.loyalty_resource_manager.address(),
+                "Wrong loyalty points",
+            );
+            assert!(
+                !loyalty_points.is_empty(),
+                "No loyalty points",
+            );
+
+            if self.loyalty_accounts.get(&user_id).is_none() {
+                self.loyalty_accounts.insert(user_id, LoyaltyAccount::new(user_id));
+            }
+
+            self.loyalty_accounts.get_mut(&user_id).unwrap().credit(loyalty_points.amount(), None);
+            loyalty_points.burn();
+        }
+
+        pub fn set_loyalty_earn_rate(
+            &mut self,
+            loyalty_earn_rate: Decimal,
+            loyalty_expiry: Option<i64>,
+        ) {
+            assert!(
+                loyalty_earn_rate >= Decimal::ZERO,
+                "Negative loyalty_earn_rate not allowed",
+            );
+            if loyalty_expiry.is_some() {
+                assert!(
+                    loyalty_expiry.unwrap() > 0,
+                    "loyalty_expiry must be positive",
+                );
+            }
+
+            self.loyalty_earn_rate = loyalty_earn_rate;
+            self.loyalty_expiry = loyalty_expiry;
+        }
+
+        pub fn set_loyalty_burn_rate(
+            &mut self,
+            coin: ResourceAddress,
+            loyalty_burn_rate: Decimal,
+        ) {
+            assert!(
+                loyalty_burn_rate >= Decimal::ZERO,
+                "Negative loyalty_burn_rate not allowed",
+            );
+
+            self.loyalty_burn_rates.insert(coin, loyalty_burn_rate);
+        }
+
+        // Gives back to the treasury the subsidy set aside for a cancelled reservation
+        fn release_subsidy(
+            &mut self,
+            item_id: u64,
+            reservation_id: u64,
+        ) {
+            let subsidy = self.items.get_mut(&item_id).expect("Item not found").get_reservation(reservation_id).release_subsidy();
+
+            self.deposit_to_treasury(subsidy);
+        }
+
+        fn deposit_to_treasury(
+            &mut self,
+            bucket: Bucket,
+        ) {
+            let coin = bucket.resource_address();
+
+            match self.treasury.get(&coin).is_some() {
+                true => self.treasury.get_mut(&coin).unwrap().put(bucket),
+                false => self.treasury.insert(coin, Vault::with_bucket(bucket)),
+            }
+        }
+
+        pub fn set_platform_fee(
+            &mut self,
+            coin: ResourceAddress,
+            percentage: Decimal,
+            flat: Decimal,
+        ) {
+            self.platform_fees.insert(coin, Fee::new(percentage, flat));
+        }
+
+        pub fn withdraw_treasury(
+            &mut self,
+            coin: ResourceAddress,
+            amount: Decimal,
+        ) -> Bucket {
+            self.treasury.get_mut(&coin).expect("No treasury for this coin").take(amount)
+        }
+
+        // Returns the id of the arbitrator after checking that the arbitrator can decide the dispute
+        fn check_arbitrator(
+            &mut self,
+            arbitrator_proof: Proof,
+            item_id: u64,
+            reservation_id: u64,
+        ) -> u64 {
+            let arbitrator = self.get_arbitrator_data(arbitrator_proof);
+
+            let (customer_id, owner_id) = self.get_parties(item_id, reservation_id);
+            assert!(
+                !arbitrator.is_conflicted(customer_id, owner_id),
+                "Conflict of interest",
+            );
+
+            self.apply_dispute_deadlines(item_id, reservation_id);
+
+            arbitrator.id
+        }
+
+        pub fn dispute_vote(
+            &mut self,
+            arbitrator_proof: Proof,
             item_id: u64,
             reservation_id: u64,
             refund_percentage: Decimal,
         ) {
-            let checked_proof = arbitrator_proof.check_with_message(
-                self.arbitrators_resource_manager.address(),
-                "Incorrect arbitrator proof",
-            ).as_non_fungible();
+            assert!(
+                self.dispute_settings.commit_window.is_none(),
+                "Votes must be committed and revealed",
+            );
+
+            self.vote(arbitrator_proof, item_id, reservation_id, refund_percentage, None);
+        }
+
+        pub fn commit_vote(
+            &mut self,
+            arbitrator_proof: Proof,
+            item_id: u64,
+            reservation_id: u64,
+            vote_hash: Hash,
+        ) {
+            let commit_window = self.dispute_settings.commit_window.expect("Commit-reveal voting not enabled");
+
+            let arbitrator_id = self.check_arbitrator(arbitrator_proof, item_id, reservation_id);
+
+            self.items.get_mut(&item_id).expect("Item not found")
+                .get_reservation(reservation_id)
+                .commit_vote(arbitrator_id, vote_hash, commit_window);
+        }
 
-            let arbitrator_id = checked_proof.non_fungible::<Arbitrator>().data().id;
+        pub fn reveal_vote(
+            &mut self,
+            arbitrator_proof: Proof,
+            item_id: u64,
+            reservation_id: u64,
+            refund_percentage: Decimal,
+            salt: String,
+        ) {
+            self.vote(arbitrator_proof, item_id, reservation_id, refund_percentage, Some(salt));
+        }
 
+        // A salt is needed to reveal a committed vote
+        fn vote(
+            &mut self,
+            arbitrator_proof: Proof,
+            item_id: u64,
+            reservation_id: u64,
+            refund_percentage: Decimal,
+            salt: Option<String>,
+        ) {
             assert!(
                 refund_percentage >= Decimal::ZERO && refund_percentage <= dec![100],
                 "refund_percentage out of 0-100 range",
             );
+            self.vote_aggregation.check_vote(refund_percentage);
 
-            self.items.get_mut(&item_id).expect("Item not found")
-                .get_reservation(reservation_id)
-                .dispute_vote(arbitrator_id, refund_percentage, self.min_arbitrators);
+            let arbitrator_id = self.check_arbitrator(arbitrator_proof, item_id, reservation_id);
+
+            let (fees, dispute_outcome) = {
+                let mut item = self.items.get_mut(&item_id).expect("Item not found");
+                let arbitration_fee = self.arbitration_fees.get(&item.coin).map(|fee| *fee);
+                let mut reservation = item.get_reservation(reservation_id);
+
+                let fees = match salt {
+                    None => reservation.dispute_vote(
+                        arbitrator_id,
+                        refund_percentage,
+                        None,
+                        arbitration_fee,
+                        self.loser_pays_arbitration,
+                        self.vote_aggregation,
+                    ),
+                    Some(salt) => reservation.reveal_vote(
+                        arbitrator_id,
+                        refund_percentage,
+                        salt,
+                        self.dispute_settings.commit_window.expect("Commit-reveal voting not enabled"),
+                        arbitration_fee,
+                        self.loser_pays_arbitration,
+                        self.vote_aggregation,
+                    ),
+                };
+
+                (fees, reservation.get_dispute_outcome())
+            };
+
+            if let Some((outcome, votes)) = dispute_outcome {
+                self.settle_stakes(reservation_id, outcome, votes);
+            }
+
+            for (arbitrator_id, fee) in fees {
+                if self.arbitrator_rewards.get(&arbitrator_id).is_none() {
+                    self.arbitrator_rewards.insert(arbitrator_id, Rewards::new());
+                }
+                self.arbitrator_rewards.get_mut(&arbitrator_id).unwrap().deposit(fee);
+            }
         }
     }
 }
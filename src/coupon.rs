@@ -0,0 +1,162 @@
+use scrypto::prelude::*;
+use scrypto::prelude::rust::cmp;
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct NewCouponEvent {
+    code: String,
+    creator_id: Option<u64>,
+    percentage: Decimal,
+    flat: Decimal,
+    valid_from: i64,
+    valid_until: i64,
+    max_uses: Option<u32>,
+    max_uses_per_user: Option<u32>,
+    item_ids: Vec<u64>,
+    host_ids: Vec<u64>,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct CouponDisabledEvent {
+    code: String,
+}
+
+#[derive(ScryptoSbor)]
+pub struct Coupon {
+    code: String,
+    // None for coupons issued by the platform, the discount is then paid by the treasury
+    pub creator_id: Option<u64>,
+    percentage: Decimal,
+    flat: Decimal,
+    valid_from: i64,
+    valid_until: i64,
+    max_uses: Option<u32>,
+    uses: u32,
+    max_uses_per_user: Option<u32>,
+    uses_per_user: KeyValueStore<u64, u32>,
+    item_ids: Vec<u64>,
+    host_ids: Vec<u64>,
+    enabled: bool,
+}
+
+impl Coupon {
+
+    pub fn new(
+        code: String,
+        creator_id: Option<u64>,
+        percentage: Decimal,
+        flat: Decimal,
+        valid_from: i64,
+        valid_until: i64,
+        max_uses: Option<u32>,
+        max_uses_per_user: Option<u32>,
+        item_ids: Vec<u64>,
+        host_ids: Vec<u64>,
+    ) -> Coupon {
+        assert!(
+            percentage >= Decimal::ZERO && percentage <= dec![100],
+            "percentage out of 0-100 range",
+        );
+        assert!(
+            flat >= Decimal::ZERO,
+            "Negative flat discount not allowed",
+        );
+        assert!(
+            valid_until > valid_from,
+            "Empty validity window",
+        );
+
+        Runtime::emit_event(
+            NewCouponEvent {
+                code: code.clone(),
+                creator_id: creator_id,
+                percentage: percentage,
+                flat: flat,
+                valid_from: valid_from,
+                valid_until: valid_until,
+                max_uses: max_uses,
+                max_uses_per_user: max_uses_per_user,
+                item_ids: item_ids.clone(),
+                host_ids: host_ids.clone(),
+            }
+        );
+
+        Self {
+            code: code,
+            creator_id: creator_id,
+            percentage: percentage,
+            flat: flat,
+            valid_from: valid_from,
+            valid_until: valid_until,
+            max_uses: max_uses,
+            uses: 0,
+            max_uses_per_user: max_uses_per_user,
+            uses_per_user: KeyValueStore::new(),
+            item_ids: item_ids,
+            host_ids: host_ids,
+            enabled: true,
+        }
+    }
+
+    pub fn disable(
+        &mut self,
+    ) {
+        self.enabled = false;
+
+        Runtime::emit_event(
+            CouponDisabledEvent {
+                code: self.code.clone(),
+            }
+        );
+    }
+
+    // Checks that the coupon can be used and returns the discount on price
+    pub fn redeem(
+        &mut self,
+        user_id: u64,
+        item_id: u64,
+        host_id: u64,
+        price: Decimal,
+    ) -> Decimal {
+        assert!(
+            self.enabled,
+            "Coupon disabled",
+        );
+
+        let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+        assert!(
+            now >= self.valid_from && now < self.valid_until,
+            "Coupon not valid now",
+        );
+
+        assert!(
+            self.item_ids.is_empty() || self.item_ids.contains(&item_id),
+            "Coupon not valid for this item",
+        );
+        assert!(
+            self.host_ids.is_empty() || self.host_ids.contains(&host_id),
+            "Coupon not valid for this host",
+        );
+
+        if self.max_uses.is_some() {
+            assert!(
+                self.uses < self.max_uses.unwrap(),
+                "Coupon usage limit reached",
+            );
+        }
+        let user_uses = self.uses_per_user.get(&user_id).map(|uses| *uses).unwrap_or(0);
+        if self.max_uses_per_user.is_some() {
+            assert!(
+                user_uses < self.max_uses_per_user.unwrap(),
+                "Coupon usage limit reached for this user",
+            );
+        }
+
+        self.uses += 1;
+        self.uses_per_user.insert(user_id, user_uses + 1);
+
+        cmp::min(
+            price,
+            price * self.percentage / dec![100] + self.flat,
+        )
+    }
+}
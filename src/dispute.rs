@@ -0,0 +1,254 @@
+use scrypto::prelude::*;
+use crate::review::*;
+
+// What happens to a dispute the arbitrators didn't decide in time
+#[derive(Debug, ScryptoSbor, PartialEq, Clone, Copy)]
+pub enum DefaultOutcome {
+    FullRefund,
+    FullPayment,
+    Split,
+}
+
+// How the refund percentage is derived from the arbitrators' votes
+#[derive(Debug, ScryptoSbor, PartialEq, Clone, Copy)]
+pub enum VoteAggregation {
+    Mean,
+    Median,
+    // Mean after discarding this percentage of the votes at each end
+    TrimmedMean(u8),
+    // Votes can only be 0, 50 or 100; the most voted one wins, a tie splits the amount in half
+    Majority,
+}
+
+// An off-ledger document anchored to a dispute
+#[derive(Debug, ScryptoSbor, Clone)]
+pub struct Evidence {
+    pub content_hash: Hash,
+    pub uri: String,
+    pub timestamp: i64,
+    pub submitter: Party,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct DisputeSettingsEvent {
+    settings: DisputeSettings,
+}
+
+// All of the windows are in seconds, None means no time limit
+#[derive(Debug, ScryptoSbor, Clone, Copy)]
+pub struct DisputeSettings {
+    // How long after the end of a reservation a dispute can be opened
+    pub dispute_window: Option<i64>,
+    // How long the parties have to settle before the dispute goes to arbitration
+    pub owner_response_window: Option<i64>,
+    // How long after the start of the dispute the parties can submit evidence
+    pub evidence_window: Option<i64>,
+    // How long the arbitrators have to decide before the default outcome is applied
+    pub arbitration_window: Option<i64>,
+    // How long a panelist has to vote before being replaced
+    pub vote_window: Option<i64>,
+    // None for public votes, otherwise how long the panelists have to commit their votes before
+    // revealing them
+    pub commit_window: Option<i64>,
+    pub default_outcome: DefaultOutcome,
+}
+
+impl VoteAggregation {
+
+    pub fn check(
+        &self,
+    ) {
+        if let VoteAggregation::TrimmedMean(percentage) = self {
+            assert!(
+                *percentage < 50,
+                "Trimmed percentage must be below 50",
+            );
+        }
+    }
+
+    pub fn check_vote(
+        &self,
+        refund_percentage: Decimal,
+    ) {
+        assert!(
+            *self != VoteAggregation::Majority ||
+            refund_percentage == Decimal::ZERO || refund_percentage == dec![50] || refund_percentage == dec![100],
+            "Only 0, 50 or 100 allowed",
+        );
+    }
+
+    pub fn aggregate(
+        &self,
+        mut votes: Vec<Decimal>,
+    ) -> Decimal {
+        votes.sort();
+        let n = votes.len();
+
+        match self {
+            VoteAggregation::Mean => votes.iter().fold(Decimal::ZERO, |sum, vote| sum + *vote) / n,
+
+            VoteAggregation::Median => match n % 2 {
+                1 => votes[n / 2],
+                _ => (votes[n / 2 - 1] + votes[n / 2]) / 2,
+            },
+
+            VoteAggregation::TrimmedMean(percentage) => {
+                let trim = n * *percentage as usize / 100;
+                VoteAggregation::Mean.aggregate(votes[trim..n - trim].to_vec())
+            },
+
+            VoteAggregation::Majority => {
+                let mut counts: BTreeMap<Decimal, usize> = BTreeMap::new();
+                for vote in votes {
+                    *counts.entry(vote).or_insert(0) += 1;
+                }
+                let max_count = *counts.values().max().unwrap();
+                let winners: Vec<Decimal> = counts.into_iter()
+                    .filter(|(_, count)| *count == max_count)
+                    .map(|(vote, _)| vote)
+                    .collect();
+
+                match winners.len() {
+                    1 => winners[0],
+                    _ => dec![50],
+                }
+            },
+        }
+    }
+}
+
+impl DisputeSettings {
+
+    pub fn new(
+        dispute_window: Option<i64>,
+        owner_response_window: Option<i64>,
+        evidence_window: Option<i64>,
+        arbitration_window: Option<i64>,
+        vote_window: Option<i64>,
+        commit_window: Option<i64>,
+        default_outcome: DefaultOutcome,
+    ) -> DisputeSettings {
+        for window in [dispute_window, owner_response_window, evidence_window, arbitration_window, vote_window, commit_window] {
+            assert!(
+                window.unwrap_or(0) >= 0,
+                "Negative time windows not allowed",
+            );
+        }
+        // Panelists who don't reveal their votes are only replaced once the vote_window expires
+        assert!(
+            commit_window.is_none() || vote_window.map_or(false, |vote_window| vote_window > commit_window.unwrap()),
+            "vote_window must be set and longer than commit_window",
+        );
+
+        let settings = Self {
+            dispute_window: dispute_window,
+            owner_response_window: owner_response_window,
+            evidence_window: evidence_window,
+            arbitration_window: arbitration_window,
+            vote_window: vote_window,
+            commit_window: commit_window,
+            default_outcome: default_outcome,
+        };
+
+        Runtime::emit_event(
+            DisputeSettingsEvent {
+                settings: settings,
+            }
+        );
+
+        settings
+    }
+
+    pub fn none() -> DisputeSettings {
+        Self {
+            dispute_window: None,
+            owner_response_window: None,
+            evidence_window: None,
+            arbitration_window: None,
+            vote_window: None,
+            commit_window: None,
+            default_outcome: DefaultOutcome::Split,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn votes(
+        votes: &[u32],
+    ) -> Vec<Decimal> {
+        votes.iter().map(|vote| Decimal::from(*vote)).collect()
+    }
+
+    #[test]
+    fn test_mean() {
+        assert_eq!(VoteAggregation::Mean.aggregate(votes(&[60, 30])), dec![45]);
+        assert_eq!(VoteAggregation::Mean.aggregate(votes(&[100, 0, 50])), dec![50]);
+        assert_eq!(VoteAggregation::Mean.aggregate(votes(&[20])), dec![20]);
+    }
+
+    #[test]
+    fn test_median() {
+        // Odd number of votes: the middle one, whatever the order they came in
+        assert_eq!(VoteAggregation::Median.aggregate(votes(&[100, 0, 30])), dec![30]);
+        // Even number of votes: the mean of the two middle ones
+        assert_eq!(VoteAggregation::Median.aggregate(votes(&[100, 0, 40, 20])), dec![30]);
+        assert_eq!(VoteAggregation::Median.aggregate(votes(&[0, 100])), dec![50]);
+    }
+
+    #[test]
+    fn test_trimmed_mean() {
+        // 10% of 10 votes: the lowest and the highest are discarded
+        assert_eq!(
+            VoteAggregation::TrimmedMean(10).aggregate(votes(&[0, 40, 40, 40, 40, 40, 40, 40, 40, 100])),
+            dec![40],
+        );
+        // 10% of 5 votes rounds down to nothing discarded
+        assert_eq!(VoteAggregation::TrimmedMean(10).aggregate(votes(&[0, 0, 0, 0, 100])), dec![20]);
+        // 49% of 5 votes discards 2 votes at each end
+        assert_eq!(VoteAggregation::TrimmedMean(49).aggregate(votes(&[0, 0, 70, 100, 100])), dec![70]);
+        assert_eq!(VoteAggregation::TrimmedMean(0).aggregate(votes(&[0, 50])), dec![25]);
+    }
+
+    #[test]
+    fn test_majority() {
+        assert_eq!(VoteAggregation::Majority.aggregate(votes(&[100, 0, 100])), dec![100]);
+        assert_eq!(VoteAggregation::Majority.aggregate(votes(&[0, 50, 0, 100])), Decimal::ZERO);
+        assert_eq!(VoteAggregation::Majority.aggregate(votes(&[50])), dec![50]);
+    }
+
+    #[test]
+    fn test_majority_tie() {
+        assert_eq!(VoteAggregation::Majority.aggregate(votes(&[0, 100])), dec![50]);
+        assert_eq!(VoteAggregation::Majority.aggregate(votes(&[100, 0, 50])), dec![50]);
+        assert_eq!(VoteAggregation::Majority.aggregate(votes(&[100, 0, 0, 100])), dec![50]);
+    }
+
+    #[test]
+    fn test_check() {
+        VoteAggregation::Mean.check();
+        VoteAggregation::TrimmedMean(49).check();
+    }
+
+    #[test]
+    #[should_panic(expected = "Trimmed percentage must be below 50")]
+    fn test_check_trimmed_percentage() {
+        VoteAggregation::TrimmedMean(50).check();
+    }
+
+    #[test]
+    fn test_check_vote() {
+        VoteAggregation::Mean.check_vote(dec![33]);
+        VoteAggregation::Majority.check_vote(Decimal::ZERO);
+        VoteAggregation::Majority.check_vote(dec![50]);
+        VoteAggregation::Majority.check_vote(dec![100]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only 0, 50 or 100 allowed")]
+    fn test_check_majority_vote() {
+        VoteAggregation::Majority.check_vote(dec![33]);
+    }
+}
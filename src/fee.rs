@@ -0,0 +1,41 @@
+use scrypto::prelude::*;
+use scrypto::prelude::rust::cmp;
+
+#[derive(Debug, ScryptoSbor, Clone, Copy)]
+pub struct Fee {
+    pub percentage: Decimal,
+    pub flat: Decimal,
+}
+
+impl Fee {
+
+    pub fn new(
+        percentage: Decimal,
+        flat: Decimal,
+    ) -> Fee {
+        assert!(
+            percentage >= Decimal::ZERO && percentage <= dec![100],
+            "percentage out of 0-100 range",
+        );
+        assert!(
+            flat >= Decimal::ZERO,
+            "Negative flat fee not allowed",
+        );
+
+        Self {
+            percentage: percentage,
+            flat: flat,
+        }
+    }
+
+    // The fee never exceeds the amount it is applied to
+    pub fn compute(
+        &self,
+        amount: Decimal,
+    ) -> Decimal {
+        cmp::min(
+            amount,
+            amount * self.percentage / dec![100] + self.flat,
+        )
+    }
+}
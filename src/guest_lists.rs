@@ -0,0 +1,93 @@
+use scrypto::prelude::*;
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct GuestBlocklistEvent {
+    owner_id: u64,
+    guest_id: u64,
+    blocked: bool,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct GuestAllowlistEvent {
+    owner_id: u64,
+    guest_id: u64,
+    allowed: bool,
+}
+
+// Customers an owner refuses, and customers allowed to book the owner's private items
+#[derive(ScryptoSbor)]
+pub struct GuestLists {
+    owner_id: u64,
+    blocklist: KeyValueStore<u64, ()>,
+    allowlist: KeyValueStore<u64, ()>,
+}
+
+impl GuestLists {
+
+    pub fn new(
+        owner_id: u64,
+    ) -> GuestLists {
+        Self {
+            owner_id: owner_id,
+            blocklist: KeyValueStore::new(),
+            allowlist: KeyValueStore::new(),
+        }
+    }
+
+    pub fn set_blocked(
+        &mut self,
+        guest_id: u64,
+        blocked: bool,
+    ) {
+        match blocked {
+            true => self.blocklist.insert(guest_id, ()),
+            false => {
+                self.blocklist.remove(&guest_id);
+            },
+        }
+
+        Runtime::emit_event(
+            GuestBlocklistEvent {
+                owner_id: self.owner_id,
+                guest_id: guest_id,
+                blocked: blocked,
+            }
+        );
+    }
+
+    pub fn set_allowed(
+        &mut self,
+        guest_id: u64,
+        allowed: bool,
+    ) {
+        match allowed {
+            true => self.allowlist.insert(guest_id, ()),
+            false => {
+                self.allowlist.remove(&guest_id);
+            },
+        }
+
+        Runtime::emit_event(
+            GuestAllowlistEvent {
+                owner_id: self.owner_id,
+                guest_id: guest_id,
+                allowed: allowed,
+            }
+        );
+    }
+
+    pub fn check_guest(
+        &self,
+        guest_id: u64,
+        private_item: bool,
+    ) {
+        assert!(
+            self.blocklist.get(&guest_id).is_none(),
+            "The owner doesn't accept your reservations",
+        );
+        assert!(
+            !private_item || self.allowlist.get(&guest_id).is_some(),
+            "This item is private",
+        );
+    }
+}
@@ -1,6 +1,9 @@
 use scrypto::prelude::*;
 use scrypto::prelude::rust::cmp;
 use crate::reservation::*;
+use crate::coupon::*;
+use crate::pricing::*;
+use crate::review::*;
 
 #[derive(Debug, ScryptoSbor)]
 struct AvailabilityInterval {
@@ -9,28 +12,69 @@ struct AvailabilityInterval {
     price_per_minimum_reservation_period: Option<Decimal>,
 }
 
+// What an item owner can allow another user to do on their behalf
+#[derive(Debug, ScryptoSbor, PartialEq, Clone, Copy)]
+pub enum DelegatePermission {
+    ManageAvailability,
+    CancelReservations,
+    OfferRefunds,
+    CollectPayments,
+}
+
 #[derive(ScryptoSbor)]
 pub struct Item {
     id: u64,
     pub owner_id: u64,
+    // Items of an organisation are managed by its members and paid to its treasury
+    pub organisation_id: Option<u64>,
     minimum_reservation_period: i64,
-    coin: ResourceAddress,
+    pub coin: ResourceAddress,
     availability_intervals: KeyValueStore<i64, AvailabilityInterval>,
     availability_interval_list: Vec<i64>,
     reservations: KeyValueStore<u64, Reservation>,
     reservation_list: Vec<u64>,
     min_cancellation_forewarning: i64,
+    pricing_rules: PricingRules,
+    dynamic_pricing: Option<DynamicPricing>,
+    pub rating: Rating,
+    pub verified_guests_only: bool,
+    pub private: bool,
+    pub delisted: bool,
+    // Added to the price of every reservation and returned to the customer if not claimed by the
+    // owner
+    pub security_deposit: Decimal,
+    delegates: KeyValueStore<u64, Vec<DelegatePermission>>,
 }
 
 #[derive(ScryptoSbor, ScryptoEvent)]
 pub struct NewItemEvent {
     item_id: u64,
     owner_id: u64,
+    organisation_id: Option<u64>,
     minimum_reservation_period: i64,
     coin: ResourceAddress,
     min_cancellation_forewarning: i64,
 }
 
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ItemDelistEvent {
+    item_id: u64,
+    reason_code: u16,
+    cancelled_reservations: Vec<u64>,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ItemRelistEvent {
+    item_id: u64,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ItemDelegateEvent {
+    item_id: u64,
+    delegate_id: u64,
+    permissions: Vec<DelegatePermission>,
+}
+
 #[derive(ScryptoSbor, ScryptoEvent)]
 pub struct NewAvailabilityIntervalEvent {
     item_id: u64,
@@ -52,6 +96,7 @@ impl Item {
     pub fn new(
         id: u64,
         owner_id: u64,
+        organisation_id: Option<u64>,
         minimum_reservation_period: i64,
         coin: ResourceAddress,
         min_cancellation_forewarning: i64,
@@ -70,6 +115,7 @@ impl Item {
             NewItemEvent {
                 item_id: id,
                 owner_id: owner_id,
+                organisation_id: organisation_id,
                 minimum_reservation_period: minimum_reservation_period,
                 coin: coin,
                 min_cancellation_forewarning: min_cancellation_forewarning,
@@ -79,6 +125,7 @@ impl Item {
         Self {
             id: id,
             owner_id: owner_id,
+            organisation_id: organisation_id,
             minimum_reservation_period: minimum_reservation_period,
             coin: coin,
             availability_intervals: KeyValueStore::new(),
@@ -86,9 +133,54 @@ impl Item {
             reservations: KeyValueStore::new(),
             reservation_list: vec![],
             min_cancellation_forewarning: min_cancellation_forewarning,
+            pricing_rules: PricingRules::none(),
+            dynamic_pricing: None,
+            rating: Rating::new(),
+            verified_guests_only: false,
+            private: false,
+            delisted: false,
+            security_deposit: Decimal::ZERO,
+            delegates: KeyValueStore::new(),
         }
     }
 
+    // An empty permissions list revokes the delegation
+    pub fn set_delegate(
+        &mut self,
+        delegate_id: u64,
+        permissions: Vec<DelegatePermission>,
+    ) {
+        assert!(
+            delegate_id != self.owner_id,
+            "The owner can't be a delegate",
+        );
+
+        match permissions.is_empty() {
+            true => {
+                self.delegates.remove(&delegate_id);
+            },
+            false => {
+                self.delegates.insert(delegate_id, permissions.clone());
+            },
+        }
+
+        Runtime::emit_event(
+            ItemDelegateEvent {
+                item_id: self.id,
+                delegate_id: delegate_id,
+                permissions: permissions,
+            }
+        );
+    }
+
+    pub fn delegate_allowed(
+        &self,
+        user_id: u64,
+        permission: DelegatePermission,
+    ) -> bool {
+        self.delegates.get(&user_id).map(|permissions| permissions.contains(&permission)).unwrap_or(false)
+    }
+
     pub fn add_or_modify_availability_interval(
         &mut self,
         start_time: i64,
@@ -159,15 +251,48 @@ impl Item {
         }
     }
 
-    pub fn new_reservation(
+    pub fn set_pricing_rules(
         &mut self,
-        id: u64,
-        customer_id: u64,
+        pricing_rules: PricingRules,
+    ) {
+        self.pricing_rules = pricing_rules;
+    }
+
+    pub fn set_dynamic_pricing(
+        &mut self,
+        dynamic_pricing: Option<DynamicPricing>,
+    ) {
+        self.dynamic_pricing = dynamic_pricing;
+    }
+
+    // Time booked by active reservations within window seconds before start_time and after
+    // end_time
+    fn booked_time_around(
+        &self,
         start_time: i64,
         end_time: i64,
-        mut bucket: Bucket,
-        resource_manager: ResourceManager,
-    ) -> (Bucket, Bucket) {
+        window: i64,
+        now: i64,
+    ) -> i64 {
+        let mut booked_time = 0;
+
+        for reservation_id in self.reservation_list.iter() {
+            let reservation = self.reservations.get(reservation_id).unwrap();
+
+            if reservation.is_active(now) {
+                booked_time += cmp::max(0, cmp::min(reservation.end_time, start_time) - cmp::max(reservation.start_time, start_time - window));
+                booked_time += cmp::max(0, cmp::min(reservation.end_time, end_time + window) - cmp::max(reservation.start_time, end_time));
+            }
+        }
+
+        booked_time
+    }
+
+    pub fn quote(
+        &self,
+        start_time: i64,
+        end_time: i64,
+    ) -> PriceQuote {
         let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
         assert!(
             start_time > now,
@@ -185,11 +310,6 @@ impl Item {
             self.minimum_reservation_period,
         );
 
-        assert!(
-            bucket.resource_address() == self.coin,
-            "Wrong coin",
-        );
-
         let (mut index, mut availability_interval) = match self.availability_interval_list.binary_search(&start_time) {
             Ok(index) => (
                 index,
@@ -220,7 +340,7 @@ impl Item {
             "Item not available",
         );
 
-        // Search all availability_intervals for the reservation period and compute total_price
+        // Search all availability_intervals for the reservation period and compute base_price
         let mut start_time_in_period = start_time;
         let mut end_time_in_period = match index == self.availability_interval_list.len() - 1 {
             true => end_time,
@@ -229,7 +349,7 @@ impl Item {
             }
         };
 
-        let mut total_price = availability_interval.price_per_minimum_reservation_period.unwrap() * ((end_time_in_period - start_time_in_period) / self.minimum_reservation_period);
+        let mut base_price = availability_interval.price_per_minimum_reservation_period.unwrap() * ((end_time_in_period - start_time_in_period) / self.minimum_reservation_period);
 
         while end_time_in_period != end_time {
             index += 1;
@@ -248,18 +368,56 @@ impl Item {
                 }
             };
 
-            total_price += availability_interval.price_per_minimum_reservation_period.unwrap() * ((end_time_in_period - start_time_in_period) / self.minimum_reservation_period);
+            base_price += availability_interval.price_per_minimum_reservation_period.unwrap() * ((end_time_in_period - start_time_in_period) / self.minimum_reservation_period);
         }
 
+        let dynamic_pricing_multiplier = match &self.dynamic_pricing {
+            Some(dynamic_pricing) => dynamic_pricing.multiplier(
+                self.booked_time_around(start_time, end_time, dynamic_pricing.window, now)
+            ),
+            None => Decimal::ONE,
+        };
+
+        let mut quote = self.pricing_rules.apply(base_price * dynamic_pricing_multiplier, end_time - start_time, start_time - now);
+        quote.dynamic_pricing_multiplier = dynamic_pricing_multiplier;
+
+        quote
+    }
+
+    pub fn new_reservation(
+        &mut self,
+        id: u64,
+        customer_id: u64,
+        start_time: i64,
+        end_time: i64,
+        mut bucket: Bucket,
+        resource_manager: ResourceManager,
+        coupon_code: Option<String>,
+        coupon: Option<&mut Coupon>,
+        loyalty_discount: Decimal,
+        treasury: Option<&mut Vault>,
+    ) -> (Bucket, Bucket) {
+        let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+
+        assert!(
+            bucket.resource_address() == self.coin,
+            "Wrong coin",
+        );
+
+        assert!(
+            !self.delisted,
+            "Item delisted",
+        );
+
+        let mut quote = self.quote(start_time, end_time);
+
         // Remove past reservations from the list and check that no existing reservation is
         // conflicting with the new one
         self.reservation_list.retain(|reservation_id| {
 
             let existing_reservation = self.reservations.get(reservation_id).unwrap();
 
-            if existing_reservation.end_time < now ||
-                existing_reservation.status == ReservationStatus::CustomerCancelled || 
-                existing_reservation.status == ReservationStatus::OwnerCancelled {
+            if !existing_reservation.is_active(now) {
                 false
             } else {
                 assert!(
@@ -270,7 +428,38 @@ impl Item {
             }
         });
 
-        //TODO: discounts, fees...
+        let mut subsidy = match coupon {
+            Some(coupon) => {
+                quote.apply_coupon_discount(
+                    coupon.redeem(customer_id, self.id, self.owner_id, quote.total_price)
+                );
+
+                match coupon.creator_id {
+                    Some(_) => Decimal::ZERO,
+                    None => quote.coupon_discount,
+                }
+            },
+            None => Decimal::ZERO,
+        };
+
+        if loyalty_discount > Decimal::ZERO {
+            quote.apply_loyalty_discount(loyalty_discount);
+            subsidy += loyalty_discount;
+        }
+
+        // The subsidy is set aside from the treasury so that the owner can always be paid
+        let subsidy = match subsidy > Decimal::ZERO {
+            true => {
+                let treasury = treasury.expect("No treasury for this coin");
+                assert!(
+                    treasury.amount() >= subsidy,
+                    "Not enough funds in the treasury",
+                );
+
+                Some(treasury.take(subsidy))
+            },
+            false => None,
+        };
 
         let (reservation, reservation_nft) = Reservation::new(
             id,
@@ -278,8 +467,12 @@ impl Item {
             customer_id,
             start_time,
             end_time,
-            bucket.take(total_price),
+            bucket.take(quote.total_price + self.security_deposit),
+            self.security_deposit,
             start_time - self.min_cancellation_forewarning,
+            coupon_code,
+            quote,
+            subsidy,
         );
         let reservation_bucket = resource_manager.mint_non_fungible(
             &NonFungibleLocalId::integer(id.into()),
@@ -292,6 +485,62 @@ impl Item {
         (reservation_bucket, bucket)
     }
 
+    // Cancels all of the future reservations so that customers can get a refund and returns their
+    // ids
+    pub fn delist(
+        &mut self,
+        reason_code: u16,
+    ) -> Vec<u64> {
+        self.delisted = true;
+
+        let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+        let mut cancelled_reservations = vec![];
+
+        // Future reservations are cancelled even if disputed, the customers get a full refund
+        // through get_refund
+        for reservation_id in self.reservation_list.iter() {
+            let mut reservation = self.reservations.get_mut(reservation_id).unwrap();
+
+            if (
+                reservation.status == ReservationStatus::Booked ||
+                reservation.status == ReservationStatus::Disputing ||
+                reservation.status == ReservationStatus::Arbitration
+            ) && reservation.start_time > now {
+                reservation.cancellation_by_owner();
+                cancelled_reservations.push(*reservation_id);
+            }
+        }
+
+        Runtime::emit_event(
+            ItemDelistEvent {
+                item_id: self.id,
+                reason_code: reason_code,
+                cancelled_reservations: cancelled_reservations.clone(),
+            }
+        );
+
+        cancelled_reservations
+    }
+
+    pub fn relist(
+        &mut self,
+    ) {
+        self.delisted = false;
+
+        Runtime::emit_event(
+            ItemRelistEvent {
+                item_id: self.id,
+            }
+        );
+    }
+
+    pub fn get_reservation_ref(
+        &self,
+        reservation_id: u64,
+    ) -> KeyValueEntryRef<'_, Reservation> {
+        self.reservations.get(&reservation_id).expect("Reservation not found")
+    }
+
     pub fn get_reservation(
         &mut self,
         reservation_id: u64,
@@ -2,4 +2,14 @@ mod user;
 mod item;
 mod reservation;
 mod arbitrator;
+mod fee;
+mod coupon;
+mod pricing;
+mod loyalty;
+mod rewards;
+mod review;
+mod guest_lists;
+mod moderation;
+mod organisation;
+pub mod dispute;
 pub mod booking_system;
@@ -0,0 +1,142 @@
+use scrypto::prelude::*;
+use scrypto::prelude::rust::cmp;
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct LoyaltyPointsCreditEvent {
+    user_id: u64,
+    amount: Decimal,
+    expiry_time: Option<i64>,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct LoyaltyPointsClaimEvent {
+    user_id: u64,
+    amount: Decimal,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct LoyaltyPointsRedeemEvent {
+    user_id: u64,
+    amount: Decimal,
+}
+
+#[derive(Debug, ScryptoSbor)]
+struct LoyaltyCredit {
+    amount: Decimal,
+    expiry_time: Option<i64>,
+}
+
+// Loyalty points credited to a user and not claimed or redeemed yet
+#[derive(Debug, ScryptoSbor)]
+pub struct LoyaltyAccount {
+    user_id: u64,
+    credits: Vec<LoyaltyCredit>,
+}
+
+impl LoyaltyCredit {
+
+    fn is_expired(
+        &self,
+        now: i64,
+    ) -> bool {
+        match self.expiry_time {
+            Some(expiry_time) => expiry_time < now,
+            None => false,
+        }
+    }
+}
+
+impl LoyaltyAccount {
+
+    pub fn new(
+        user_id: u64,
+    ) -> LoyaltyAccount {
+        Self {
+            user_id: user_id,
+            credits: vec![],
+        }
+    }
+
+    pub fn credit(
+        &mut self,
+        amount: Decimal,
+        expiry_time: Option<i64>,
+    ) {
+        let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+        self.credits.retain(|credit| !credit.is_expired(now));
+
+        self.credits.push(
+            LoyaltyCredit {
+                amount: amount,
+                expiry_time: expiry_time,
+            }
+        );
+
+        Runtime::emit_event(
+            LoyaltyPointsCreditEvent {
+                user_id: self.user_id,
+                amount: amount,
+                expiry_time: expiry_time,
+            }
+        );
+    }
+
+    // Expired credits can't be redeemed, the ones closest to expiry are used first
+    pub fn redeem(
+        &mut self,
+        amount: Decimal,
+    ) {
+        assert!(
+            amount > Decimal::ZERO,
+            "Wrong amount",
+        );
+
+        let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+        self.credits.retain(|credit| !credit.is_expired(now));
+        self.credits.sort_by_key(|credit| credit.expiry_time.unwrap_or(i64::MAX));
+
+        let mut to_redeem = amount;
+        for credit in self.credits.iter_mut() {
+            let redeemed = cmp::min(credit.amount, to_redeem);
+            credit.amount -= redeemed;
+            to_redeem -= redeemed;
+        }
+        assert!(
+            to_redeem == Decimal::ZERO,
+            "Not enough loyalty points",
+        );
+        self.credits.retain(|credit| credit.amount > Decimal::ZERO);
+
+        Runtime::emit_event(
+            LoyaltyPointsRedeemEvent {
+                user_id: self.user_id,
+                amount: amount,
+            }
+        );
+    }
+
+    // Returns the amount of loyalty points to mint; expired credits are lost, claimed points
+    // don't expire
+    pub fn claim(
+        &mut self,
+    ) -> Decimal {
+        let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+
+        let amount = self.credits.drain(..)
+            .filter(|credit| !credit.is_expired(now))
+            .fold(Decimal::ZERO, |amount, credit| amount + credit.amount);
+        assert!(
+            amount > Decimal::ZERO,
+            "No loyalty points",
+        );
+
+        Runtime::emit_event(
+            LoyaltyPointsClaimEvent {
+                user_id: self.user_id,
+                amount: amount,
+            }
+        );
+
+        amount
+    }
+}
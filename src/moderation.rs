@@ -0,0 +1,71 @@
+use scrypto::prelude::*;
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct UserSuspensionEvent {
+    user_id: u64,
+    reason_code: u16,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct UserUnsuspensionEvent {
+    user_id: u64,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct AppealEvent {
+    user_id: u64,
+    item_id: Option<u64>,
+    content_hash: Hash,
+}
+
+// A suspended user can't create items, book or get payments; refunds are still possible
+#[derive(Debug, ScryptoSbor)]
+pub struct Suspension {
+    user_id: u64,
+    reason_code: u16,
+}
+
+impl Suspension {
+
+    pub fn new(
+        user_id: u64,
+        reason_code: u16,
+    ) -> Suspension {
+
+        Runtime::emit_event(
+            UserSuspensionEvent {
+                user_id: user_id,
+                reason_code: reason_code,
+            }
+        );
+
+        Self {
+            user_id: user_id,
+            reason_code: reason_code,
+        }
+    }
+
+    pub fn lift(
+        self,
+    ) {
+        Runtime::emit_event(
+            UserUnsuspensionEvent {
+                user_id: self.user_id,
+            }
+        );
+    }
+}
+
+pub fn appeal(
+    user_id: u64,
+    item_id: Option<u64>,
+    content_hash: Hash,
+) {
+    Runtime::emit_event(
+        AppealEvent {
+            user_id: user_id,
+            item_id: item_id,
+            content_hash: content_hash,
+        }
+    );
+}
@@ -0,0 +1,110 @@
+use scrypto::prelude::*;
+use crate::item::*;
+use crate::rewards::*;
+
+// Admins can do anything, managers can run the organisation items, staff can only manage their
+// availability
+#[derive(Debug, ScryptoSbor, PartialEq, Clone, Copy)]
+pub enum OrganisationRole {
+    Admin,
+    Manager,
+    Staff,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct NewOrganisationEvent {
+    organisation_id: u64,
+    name: String,
+    admin_id: u64,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct OrganisationMemberEvent {
+    organisation_id: u64,
+    user_id: u64,
+    role: Option<OrganisationRole>,
+}
+
+#[derive(ScryptoSbor)]
+pub struct Organisation {
+    id: u64,
+    name: String,
+    members: KeyValueStore<u64, OrganisationRole>,
+    // Payments for the organisation items
+    pub treasury: Rewards,
+}
+
+impl Organisation {
+
+    pub fn new(
+        id: u64,
+        name: String,
+        admin_id: u64,
+    ) -> Organisation {
+        Runtime::emit_event(
+            NewOrganisationEvent {
+                organisation_id: id,
+                name: name.clone(),
+                admin_id: admin_id,
+            }
+        );
+
+        let organisation = Self {
+            id: id,
+            name: name,
+            members: KeyValueStore::new(),
+            treasury: Rewards::new(),
+        };
+        organisation.members.insert(admin_id, OrganisationRole::Admin);
+
+        organisation
+    }
+
+    // A None role removes the member from the organisation
+    pub fn set_member(
+        &mut self,
+        admin_id: u64,
+        user_id: u64,
+        role: Option<OrganisationRole>,
+    ) {
+        assert!(
+            self.allows(admin_id, None),
+            "You are not an admin of this organisation",
+        );
+        assert!(
+            user_id != admin_id,
+            "You can't change your own role",
+        );
+
+        match role {
+            Some(role) => {
+                self.members.insert(user_id, role);
+            },
+            None => {
+                self.members.remove(&user_id);
+            },
+        }
+
+        Runtime::emit_event(
+            OrganisationMemberEvent {
+                organisation_id: self.id,
+                user_id: user_id,
+                role: role,
+            }
+        );
+    }
+
+    // None for the actions reserved to admins
+    pub fn allows(
+        &self,
+        user_id: u64,
+        permission: Option<DelegatePermission>,
+    ) -> bool {
+        match self.members.get(&user_id).map(|role| *role) {
+            Some(OrganisationRole::Admin) => true,
+            Some(OrganisationRole::Manager) => permission.is_some(),
+            Some(OrganisationRole::Staff) => permission == Some(DelegatePermission::ManageAvailability),
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use scrypto::prelude::*;
+use scrypto::prelude::rust::cmp;
+
+const WEEK: i64 = 604800;
+const MONTH: i64 = 2592000;
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct PricingRulesEvent {
+    item_id: u64,
+    rules: PricingRules,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct DynamicPricingEvent {
+    item_id: u64,
+    dynamic_pricing: Option<DynamicPricing>,
+}
+
+#[derive(Debug, ScryptoSbor, Clone)]
+pub struct PricingRules {
+    weekly_discount_percentage: Decimal,
+    monthly_discount_percentage: Decimal,
+    early_bird_advance: i64,
+    early_bird_discount_percentage: Decimal,
+    last_minute_window: i64,
+    // Negative for a discount, positive for a surcharge
+    last_minute_percentage: Decimal,
+    service_fee: Decimal,
+}
+
+// The price is multiplied by a factor growing linearly from min_multiplier to max_multiplier
+// with the fraction of time booked within window seconds before and after the reservation
+#[derive(Debug, ScryptoSbor, Clone)]
+pub struct DynamicPricing {
+    pub window: i64,
+    min_multiplier: Decimal,
+    max_multiplier: Decimal,
+}
+
+#[derive(Debug, ScryptoSbor, Clone)]
+pub struct PriceQuote {
+    pub dynamic_pricing_multiplier: Decimal,
+    pub base_price: Decimal,
+    pub length_of_stay_discount: Decimal,
+    pub early_bird_discount: Decimal,
+    pub last_minute_adjustment: Decimal,
+    pub service_fee: Decimal,
+    pub coupon_discount: Decimal,
+    pub loyalty_discount: Decimal,
+    pub total_price: Decimal,
+}
+
+impl PricingRules {
+
+    pub fn new(
+        item_id: u64,
+        weekly_discount_percentage: Decimal,
+        monthly_discount_percentage: Decimal,
+        early_bird_advance: i64,
+        early_bird_discount_percentage: Decimal,
+        last_minute_window: i64,
+        last_minute_percentage: Decimal,
+        service_fee: Decimal,
+    ) -> PricingRules {
+        for percentage in [weekly_discount_percentage, monthly_discount_percentage, early_bird_discount_percentage] {
+            assert!(
+                percentage >= Decimal::ZERO && percentage <= dec![100],
+                "Discount percentage out of 0-100 range",
+            );
+        }
+        assert!(
+            last_minute_percentage >= dec![-100] && last_minute_percentage <= dec![100],
+            "last_minute_percentage out of -100-100 range",
+        );
+        assert!(
+            early_bird_advance >= 0 && last_minute_window >= 0,
+            "Negative time windows not allowed",
+        );
+        assert!(
+            service_fee >= Decimal::ZERO,
+            "Negative service_fee not allowed",
+        );
+
+        let rules = Self {
+            weekly_discount_percentage: weekly_discount_percentage,
+            monthly_discount_percentage: monthly_discount_percentage,
+            early_bird_advance: early_bird_advance,
+            early_bird_discount_percentage: early_bird_discount_percentage,
+            last_minute_window: last_minute_window,
+            last_minute_percentage: last_minute_percentage,
+            service_fee: service_fee,
+        };
+
+        Runtime::emit_event(
+            PricingRulesEvent {
+                item_id: item_id,
+                rules: rules.clone(),
+            }
+        );
+
+        rules
+    }
+
+    pub fn none() -> PricingRules {
+        Self {
+            weekly_discount_percentage: Decimal::ZERO,
+            monthly_discount_percentage: Decimal::ZERO,
+            early_bird_advance: 0,
+            early_bird_discount_percentage: Decimal::ZERO,
+            last_minute_window: 0,
+            last_minute_percentage: Decimal::ZERO,
+            service_fee: Decimal::ZERO,
+        }
+    }
+
+    // All of the percentages are applied to the base_price; a zero time window disables the
+    // corresponding rule
+    pub fn apply(
+        &self,
+        base_price: Decimal,
+        length: i64,
+        advance: i64,
+    ) -> PriceQuote {
+        let length_of_stay_discount = match length {
+            length if length >= MONTH => base_price * self.monthly_discount_percentage / dec![100],
+            length if length >= WEEK => base_price * self.weekly_discount_percentage / dec![100],
+            _ => Decimal::ZERO,
+        };
+
+        let early_bird_discount = match self.early_bird_advance > 0 && advance >= self.early_bird_advance {
+            true => base_price * self.early_bird_discount_percentage / dec![100],
+            false => Decimal::ZERO,
+        };
+
+        let last_minute_adjustment = match self.last_minute_window > 0 && advance <= self.last_minute_window {
+            true => base_price * self.last_minute_percentage / dec![100],
+            false => Decimal::ZERO,
+        };
+
+        let total_price = cmp::max(
+            Decimal::ZERO,
+            base_price - length_of_stay_discount - early_bird_discount + last_minute_adjustment,
+        ) + self.service_fee;
+
+        PriceQuote {
+            dynamic_pricing_multiplier: Decimal::ONE,
+            base_price: base_price,
+            length_of_stay_discount: length_of_stay_discount,
+            early_bird_discount: early_bird_discount,
+            last_minute_adjustment: last_minute_adjustment,
+            service_fee: self.service_fee,
+            coupon_discount: Decimal::ZERO,
+            loyalty_discount: Decimal::ZERO,
+            total_price: total_price,
+        }
+    }
+}
+
+impl DynamicPricing {
+
+    pub fn new(
+        item_id: u64,
+        window: i64,
+        min_multiplier: Decimal,
+        max_multiplier: Decimal,
+    ) -> Option<DynamicPricing> {
+        let dynamic_pricing = match window {
+            0 => None,
+            _ => {
+                assert!(
+                    window > 0,
+                    "Negative window not allowed",
+                );
+                assert!(
+                    min_multiplier > Decimal::ZERO,
+                    "min_multiplier must be positive",
+                );
+                assert!(
+                    max_multiplier >= min_multiplier,
+                    "max_multiplier below min_multiplier",
+                );
+
+                Some(
+                    Self {
+                        window: window,
+                        min_multiplier: min_multiplier,
+                        max_multiplier: max_multiplier,
+                    }
+                )
+            }
+        };
+
+        Runtime::emit_event(
+            DynamicPricingEvent {
+                item_id: item_id,
+                dynamic_pricing: dynamic_pricing.clone(),
+            }
+        );
+
+        dynamic_pricing
+    }
+
+    pub fn multiplier(
+        &self,
+        booked_time: i64,
+    ) -> Decimal {
+        let occupancy = Decimal::from(booked_time) / Decimal::from(2 * self.window);
+
+        self.min_multiplier + (self.max_multiplier - self.min_multiplier) * occupancy
+    }
+}
+
+impl PriceQuote {
+
+    pub fn apply_coupon_discount(
+        &mut self,
+        coupon_discount: Decimal,
+    ) {
+        self.coupon_discount = coupon_discount;
+        self.total_price -= coupon_discount;
+    }
+
+    pub fn apply_loyalty_discount(
+        &mut self,
+        loyalty_discount: Decimal,
+    ) {
+        assert!(
+            loyalty_discount <= self.total_price,
+            "Too many loyalty points for this reservation",
+        );
+
+        self.loyalty_discount = loyalty_discount;
+        self.total_price -= loyalty_discount;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 86400;
+
+    fn rules() -> PricingRules {
+        PricingRules {
+            weekly_discount_percentage: dec![10],
+            monthly_discount_percentage: dec![30],
+            early_bird_advance: MONTH,
+            early_bird_discount_percentage: dec![5],
+            last_minute_window: DAY,
+            last_minute_percentage: dec![-20],
+            service_fee: dec![2],
+        }
+    }
+
+    #[test]
+    fn test_no_rules() {
+        let quote = PricingRules::none().apply(dec![100], MONTH, 0);
+
+        assert_eq!(quote.length_of_stay_discount, Decimal::ZERO);
+        assert_eq!(quote.early_bird_discount, Decimal::ZERO);
+        assert_eq!(quote.last_minute_adjustment, Decimal::ZERO);
+        assert_eq!(quote.total_price, dec![100]);
+    }
+
+    #[test]
+    fn test_service_fee_only() {
+        let quote = rules().apply(dec![100], DAY, WEEK);
+
+        assert_eq!(quote.service_fee, dec![2]);
+        assert_eq!(quote.total_price, dec![102]);
+    }
+
+    #[test]
+    fn test_length_of_stay_thresholds() {
+        assert_eq!(rules().apply(dec![100], WEEK - 1, WEEK).length_of_stay_discount, Decimal::ZERO);
+
+        let quote = rules().apply(dec![100], WEEK, WEEK);
+        assert_eq!(quote.length_of_stay_discount, dec![10]);
+        assert_eq!(quote.total_price, dec![92]);
+
+        assert_eq!(rules().apply(dec![100], MONTH - 1, WEEK).length_of_stay_discount, dec![10]);
+
+        // The monthly discount replaces the weekly one
+        let quote = rules().apply(dec![100], MONTH, WEEK);
+        assert_eq!(quote.length_of_stay_discount, dec![30]);
+        assert_eq!(quote.total_price, dec![72]);
+    }
+
+    #[test]
+    fn test_early_bird_edge() {
+        assert_eq!(rules().apply(dec![100], DAY, MONTH - 1).early_bird_discount, Decimal::ZERO);
+
+        let quote = rules().apply(dec![100], DAY, MONTH);
+        assert_eq!(quote.early_bird_discount, dec![5]);
+        assert_eq!(quote.total_price, dec![97]);
+    }
+
+    #[test]
+    fn test_last_minute_edge() {
+        assert_eq!(rules().apply(dec![100], DAY, DAY + 1).last_minute_adjustment, Decimal::ZERO);
+
+        let quote = rules().apply(dec![100], DAY, DAY);
+        assert_eq!(quote.last_minute_adjustment, dec![-20]);
+        assert_eq!(quote.total_price, dec![82]);
+    }
+
+    #[test]
+    fn test_last_minute_surcharge() {
+        let mut rules = rules();
+        rules.last_minute_percentage = dec![50];
+
+        let quote = rules.apply(dec![100], DAY, 0);
+        assert_eq!(quote.last_minute_adjustment, dec![50]);
+        assert_eq!(quote.total_price, dec![152]);
+    }
+
+    #[test]
+    fn test_zero_windows_disable_rules() {
+        let mut rules = rules();
+        rules.early_bird_advance = 0;
+        rules.last_minute_window = 0;
+
+        let quote = rules.apply(dec![100], DAY, 0);
+        assert_eq!(quote.early_bird_discount, Decimal::ZERO);
+        assert_eq!(quote.last_minute_adjustment, Decimal::ZERO);
+        assert_eq!(quote.total_price, dec![102]);
+    }
+
+    #[test]
+    fn test_discounts_clamped_at_zero() {
+        let mut rules = rules();
+        rules.monthly_discount_percentage = dec![100];
+        rules.early_bird_discount_percentage = dec![50];
+
+        // The service fee is still due
+        let quote = rules.apply(dec![100], MONTH, MONTH);
+        assert_eq!(quote.length_of_stay_discount, dec![100]);
+        assert_eq!(quote.early_bird_discount, dec![50]);
+        assert_eq!(quote.total_price, dec![2]);
+    }
+
+    fn dynamic_pricing() -> DynamicPricing {
+        DynamicPricing {
+            window: WEEK,
+            min_multiplier: dec!["0.8"],
+            max_multiplier: dec!["1.6"],
+        }
+    }
+
+    #[test]
+    fn test_dynamic_pricing_bounds() {
+        assert_eq!(dynamic_pricing().multiplier(0), dec!["0.8"]);
+        assert_eq!(dynamic_pricing().multiplier(2 * WEEK), dec!["1.6"]);
+    }
+
+    #[test]
+    fn test_dynamic_pricing_occupancy() {
+        // The window extends on both sides of the reservation, a week booked is half of it
+        assert_eq!(dynamic_pricing().multiplier(WEEK), dec!["1.2"]);
+        assert_eq!(dynamic_pricing().multiplier(WEEK / 2), dec!["1"]);
+    }
+
+    #[test]
+    fn test_flat_dynamic_pricing() {
+        let mut dynamic_pricing = dynamic_pricing();
+        dynamic_pricing.max_multiplier = dec!["0.8"];
+
+        assert_eq!(dynamic_pricing.multiplier(WEEK), dec!["0.8"]);
+    }
+}
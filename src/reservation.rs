@@ -1,4 +1,10 @@
 use scrypto::prelude::*;
+use scrypto::prelude::rust::cmp;
+use crate::fee::*;
+use crate::pricing::*;
+use crate::review::*;
+use crate::dispute::*;
+use crate::arbitrator::*;
 
 #[derive(Debug, ScryptoSbor, PartialEq, Clone, Copy)]
 pub enum ReservationStatus {
@@ -8,6 +14,8 @@ pub enum ReservationStatus {
     Disputing,
     DisputeTerminated,
     Completed,
+    Settled,
+    Arbitration,
 }
 
 #[derive(ScryptoSbor, ScryptoEvent)]
@@ -17,6 +25,9 @@ pub struct NewReservationEvent {
     customer_id: u64,
     start_time: i64,
     end_time: i64,
+    coupon_code: Option<String>,
+    quote: PriceQuote,
+    security_deposit: Decimal,
 }
 
 #[derive(ScryptoSbor, ScryptoEvent)]
@@ -40,6 +51,33 @@ pub struct ReservationRefundEvent {
 #[derive(ScryptoSbor, ScryptoEvent)]
 pub struct ReservationDisputeEvent {
     reservation_id: u64,
+    claimant: Party,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ArbitratorPanelEvent {
+    reservation_id: u64,
+    panel: Vec<u64>,
+    replaced: Vec<u64>,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ArbitratorPanelIncompleteEvent {
+    reservation_id: u64,
+    panel_size: u16,
+    missing_panelists: u16,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct EvidenceSubmittedEvent {
+    reservation_id: u64,
+    evidence: Evidence,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct SecurityDepositReturnEvent {
+    reservation_id: u64,
+    amount: Decimal,
 }
 
 #[derive(ScryptoSbor, ScryptoEvent)]
@@ -48,12 +86,49 @@ pub struct ReservationRefundOfferEvent {
     refund_amount: Decimal,
 }
 
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ReservationCounterOfferEvent {
+    reservation_id: u64,
+    refund_amount: Decimal,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ReservationSettlementEvent {
+    reservation_id: u64,
+    accepted_by: Party,
+    refund_amount: Decimal,
+    to_owner: Decimal,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ReservationArbitrationEvent {
+    reservation_id: u64,
+    // None if the parties didn't settle in time
+    escalated_by: Option<Party>,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct DisputeDefaultOutcomeEvent {
+    reservation_id: u64,
+    outcome: DefaultOutcome,
+    refund_amount: Decimal,
+    to_owner: Decimal,
+}
+
 #[derive(ScryptoSbor, ScryptoEvent)]
 pub struct ReservationGetPaymentEvent {
     reservation_id: u64,
     old_status: ReservationStatus,
     new_status: ReservationStatus,
-    payment_amount: Decimal
+    payment_amount: Decimal,
+    fee_amount: Decimal,
+    subsidy_amount: Decimal,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct DisputeVoteCommitEvent {
+    reservation_id: u64,
+    arbitrator_id: u64,
 }
 
 #[derive(ScryptoSbor, ScryptoEvent)]
@@ -62,28 +137,67 @@ pub struct DisputeVoteEvent {
     arbitrator_id: u64,
     number_of_voters: usize,
     min_arbitrators: u16,
-    dispute_votes_sum: Decimal,
+    // None while votes can still be committed
+    dispute_votes_sum: Option<Decimal>,
 }
 
 #[derive(ScryptoSbor, ScryptoEvent)]
 pub struct DisputeVoteTerminatedEvent {
     reservation_id: u64,
+    aggregation: VoteAggregation,
+    refund_percentage: Decimal,
     refund_amount: Decimal,
     to_owner: Decimal,
+    arbitration_fee: Decimal,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ReviewSubmittedEvent {
+    reservation_id: u64,
+    item_id: u64,
+    reviewer: Party,
+    reviewer_id: u64,
+    reviewed_id: u64,
+    rating: u8,
+    content_hash: Hash,
 }
 
 #[derive(Debug, ScryptoSbor)]
 pub struct Reservation {
     id: u64,
-    customer_id: u64,
+    pub customer_id: u64,
     pub start_time: i64,
     pub end_time: i64,
     vault: Vault,
     pub status: ReservationStatus,
+    price: Decimal,
+    // Part of the vault that is not paid to the owner unless the owner wins a dispute
+    security_deposit: Decimal,
+    coupon_code: Option<String>,
+    // Part of the discount paid by the platform to the owner at payment time
+    subsidy: Vault,
+    refund_offer: Option<Decimal>,
+    counter_offer: Option<Decimal>,
+    dispute_start_time: i64,
+    arbitration_start_time: i64,
+    evidence: Vec<Evidence>,
+    // The arbitrators allowed to vote on the dispute
+    panel: Vec<u64>,
+    // The number of votes needed to terminate the dispute
+    panel_size: usize,
+    // When each panelist can start voting
+    panel_times: BTreeMap<u64, i64>,
+    replaced_panelists: Vec<u64>,
     refund_amount: Decimal,
     to_owner: Decimal,
     dispute_votes: BTreeMap<u64, Decimal>,
     dispute_votes_sum: Decimal,
+    // Hashes of the votes not revealed yet
+    vote_commits: BTreeMap<u64, Hash>,
+    // The refund percentage decided by the arbitrators
+    dispute_outcome: Option<Decimal>,
+    customer_reviewed: bool,
+    owner_reviewed: bool,
 }
 
 #[derive(Debug, ScryptoSbor, NonFungibleData)]
@@ -106,8 +220,16 @@ impl Reservation {
         start_time: i64,
         end_time: i64,
         bucket: Bucket,
+        security_deposit: Decimal,
         max_cancellation_time: i64,
+        coupon_code: Option<String>,
+        quote: PriceQuote,
+        subsidy: Option<Bucket>,
     ) -> (Reservation, ReservationNFT) {
+        let subsidy = match subsidy {
+            Some(subsidy) => Vault::with_bucket(subsidy),
+            None => Vault::new(bucket.resource_address()),
+        };
 
         Runtime::emit_event(
             NewReservationEvent {
@@ -116,21 +238,41 @@ impl Reservation {
                 customer_id: customer_id,
                 start_time: start_time,
                 end_time: end_time,
+                coupon_code: coupon_code.clone(),
+                quote: quote.clone(),
+                security_deposit: security_deposit,
             }
         );
 
         (
             Self {
                 id: id,
-                to_owner: bucket.amount(),
+                to_owner: bucket.amount() - security_deposit,
                 customer_id: customer_id,
                 start_time: start_time,
                 end_time: end_time,
+                price: bucket.amount() - security_deposit,
+                security_deposit: security_deposit,
                 vault: Vault::with_bucket(bucket),
                 status: ReservationStatus::Booked,
+                coupon_code: coupon_code,
+                subsidy: subsidy,
+                refund_offer: None,
+                counter_offer: None,
+                dispute_start_time: 0,
+                arbitration_start_time: 0,
+                evidence: vec![],
+                panel: vec![],
+                panel_size: 0,
+                panel_times: BTreeMap::new(),
+                replaced_panelists: vec![],
                 refund_amount: Decimal::ZERO,
                 dispute_votes_sum: Decimal::ZERO,
                 dispute_votes: BTreeMap::new(),
+                vote_commits: BTreeMap::new(),
+                dispute_outcome: None,
+                customer_reviewed: false,
+                owner_reviewed: false,
             },
             ReservationNFT {
                 id: id,
@@ -149,6 +291,16 @@ impl Reservation {
         )
     }
 
+    // Reservations that still occupy the item
+    pub fn is_active(
+        &self,
+        now: i64,
+    ) -> bool {
+        self.end_time >= now &&
+            self.status != ReservationStatus::CustomerCancelled &&
+            self.status != ReservationStatus::OwnerCancelled
+    }
+
     pub fn cancellation_by_customer(
         &mut self
     ) -> Bucket {
@@ -172,7 +324,8 @@ impl Reservation {
     ) {
         assert!(
             self.status == ReservationStatus::Booked ||
-            self.status == ReservationStatus::Disputing,
+            self.status == ReservationStatus::Disputing ||
+            self.status == ReservationStatus::Arbitration,
             "Wrong status",
         );
 
@@ -193,14 +346,7 @@ impl Reservation {
         let refund = match self.status {
             ReservationStatus::OwnerCancelled => self.vault.take_all(),
 
-            ReservationStatus::Disputing => {
-                self.status = ReservationStatus::DisputeTerminated;
-                let refund_amount = self.refund_amount;
-                self.refund_amount = Decimal::ZERO;
-                self.vault.take(refund_amount)
-            },
-
-            ReservationStatus::DisputeTerminated => {
+            ReservationStatus::DisputeTerminated | ReservationStatus::Settled => {
                 let refund_amount = self.refund_amount;
                 self.refund_amount = Decimal::ZERO;
                 self.vault.take(refund_amount)
@@ -223,56 +369,413 @@ impl Reservation {
 
     pub fn start_dispute(
         &mut self,
+        dispute_window: Option<i64>,
     ) {
         assert!(
             self.status == ReservationStatus::Booked,
             "Wrong status",
         );
 
+        self.open_dispute(Party::Customer, dispute_window);
+    }
+
+    // The owner can claim part of the payment and of the security deposit even after getting the
+    // payment; the claim is handled as a refund offer of the rest
+    pub fn start_dispute_as_owner(
+        &mut self,
+        claim_amount: Decimal,
+        dispute_window: Option<i64>,
+    ) {
+        assert!(
+            self.status == ReservationStatus::Booked ||
+            (self.status == ReservationStatus::Completed && self.security_deposit > Decimal::ZERO),
+            "Wrong status",
+        );
+        assert!(
+            claim_amount > Decimal::ZERO && claim_amount <= self.vault.amount(),
+            "claim_amount out of range",
+        );
+
+        self.open_dispute(Party::Owner, dispute_window);
+
+        self.offer_partial_refund(self.vault.amount() - claim_amount);
+    }
+
+    fn open_dispute(
+        &mut self,
+        claimant: Party,
+        dispute_window: Option<i64>,
+    ) {
+        let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+        if dispute_window.is_some() {
+            assert!(
+                now <= self.end_time + dispute_window.unwrap(),
+                "Too late to open a dispute",
+            );
+        }
+
         self.status = ReservationStatus::Disputing;
+        self.dispute_start_time = now;
 
         Runtime::emit_event(
             ReservationDisputeEvent {
                 reservation_id: self.id,
+                claimant: claimant,
+            }
+        );
+    }
+
+    pub fn submit_evidence(
+        &mut self,
+        submitter: Party,
+        content_hash: Hash,
+        uri: String,
+        evidence_window: Option<i64>,
+    ) {
+        assert!(
+            self.status == ReservationStatus::Disputing ||
+            self.status == ReservationStatus::Arbitration,
+            "Wrong status",
+        );
+
+        let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+        if evidence_window.is_some() {
+            assert!(
+                now <= self.dispute_start_time + evidence_window.unwrap(),
+                "Too late to submit evidence",
+            );
+        }
+
+        let evidence = Evidence {
+            content_hash: content_hash,
+            uri: uri,
+            timestamp: now,
+            submitter: submitter,
+        };
+
+        Runtime::emit_event(
+            EvidenceSubmittedEvent {
+                reservation_id: self.id,
+                evidence: evidence.clone(),
             }
         );
+
+        self.evidence.push(evidence);
+    }
+
+    // Fills the empty seats of the panel, after replacing the panelists who didn't vote in time;
+    // returns the replaced panelists
+    pub fn update_panel(
+        &mut self,
+        candidates: Vec<u64>,
+        size: usize,
+        vote_window: Option<i64>,
+        commit_window: Option<i64>,
+    ) -> Vec<u64> {
+        assert!(
+            self.status == ReservationStatus::Disputing ||
+            self.status == ReservationStatus::Arbitration,
+            "Wrong status",
+        );
+
+        let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+        self.panel_size = size;
+        let mut absent = vec![];
+        if self.status == ReservationStatus::Arbitration && vote_window.is_some() {
+            // A committed vote can't be revealed while other panelists can still commit, so it
+            // doesn't count as missing meanwhile
+            let commits_open = self.commits_open(commit_window, now);
+            let (panel, replaced): (Vec<u64>, Vec<u64>) = self.panel.iter()
+                .cloned()
+                .partition(|arbitrator_id|
+                    self.dispute_votes.contains_key(arbitrator_id) ||
+                    now <= self.panel_time(*arbitrator_id) + vote_window.unwrap() ||
+                    (commits_open && self.vote_commits.contains_key(arbitrator_id))
+                );
+            for arbitrator_id in replaced.iter() {
+                self.panel_times.remove(arbitrator_id);
+                self.vote_commits.remove(arbitrator_id);
+            }
+            self.panel = panel;
+            self.replaced_panelists.extend(replaced.iter().cloned());
+            absent = replaced;
+        }
+
+        if self.panel.len() >= size {
+            return absent;
+        }
+
+        let candidates: Vec<u64> = candidates.into_iter()
+            .filter(|arbitrator_id| !self.panel.contains(arbitrator_id) && !self.replaced_panelists.contains(arbitrator_id))
+            .collect();
+        let new_panelists = select_panel(candidates, size - self.panel.len(), self.id);
+        for arbitrator_id in new_panelists.iter() {
+            self.panel_times.insert(*arbitrator_id, now);
+        }
+        self.panel.extend(new_panelists);
+
+        Runtime::emit_event(
+            ArbitratorPanelEvent {
+                reservation_id: self.id,
+                panel: self.panel.clone(),
+                replaced: self.replaced_panelists.clone(),
+            }
+        );
+
+        // Nobody can terminate the dispute until replace_absent_panelists finds enough candidates
+        if self.panel.len() < size {
+            Runtime::emit_event(
+                ArbitratorPanelIncompleteEvent {
+                    reservation_id: self.id,
+                    panel_size: size as u16,
+                    missing_panelists: (size - self.panel.len()) as u16,
+                }
+            );
+        }
+
+        absent
+    }
+
+    fn panel_time(
+        &self,
+        arbitrator_id: u64,
+    ) -> i64 {
+        *self.panel_times.get(&arbitrator_id).expect("You are not on the panel of this dispute")
+    }
+
+    // The voting windows of the panelists assigned before the arbitration start with it
+    fn start_panel_times(
+        &mut self,
+    ) {
+        for panel_time in self.panel_times.values_mut() {
+            *panel_time = self.arbitration_start_time;
+        }
+    }
+
+    // True while some panelist who hasn't voted can still commit a vote
+    fn commits_open(
+        &self,
+        commit_window: Option<i64>,
+        now: i64,
+    ) -> bool {
+        match commit_window {
+            None => false,
+            Some(commit_window) => self.panel.iter()
+                .any(|arbitrator_id|
+                    !self.dispute_votes.contains_key(arbitrator_id) &&
+                    now <= self.panel_time(*arbitrator_id) + commit_window
+                ),
+        }
+    }
+
+    pub fn get_evidence(
+        &self,
+    ) -> Vec<Evidence> {
+        self.evidence.clone()
+    }
+
+    // The refund percentage decided by the arbitrators and their votes, once the vote terminated
+    pub fn get_dispute_outcome(
+        &self,
+    ) -> Option<(Decimal, BTreeMap<u64, Decimal>)> {
+        self.dispute_outcome.map(|outcome| (outcome, self.dispute_votes.clone()))
+    }
+
+    pub fn claim_security_deposit(
+        &mut self,
+        hold_period: i64,
+    ) -> Bucket {
+        assert!(
+            self.status == ReservationStatus::Booked ||
+            self.status == ReservationStatus::Completed,
+            "Wrong status",
+        );
+        assert!(
+            self.security_deposit > Decimal::ZERO,
+            "No security deposit",
+        );
+
+        let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+        assert!(
+            now >= self.end_time + hold_period,
+            "You can't get the security deposit now",
+        );
+
+        let amount = self.security_deposit;
+        self.security_deposit = Decimal::ZERO;
+
+        Runtime::emit_event(
+            SecurityDepositReturnEvent {
+                reservation_id: self.id,
+                amount: amount,
+            }
+        );
+
+        self.vault.take(amount)
     }
 
     pub fn offer_partial_refund(
         &mut self,
         refund_amount: Decimal,
+    ) {
+        self.check_offer(refund_amount);
+
+        self.refund_offer = Some(refund_amount);
+
+        Runtime::emit_event(
+            ReservationRefundOfferEvent {
+                reservation_id: self.id,
+                refund_amount: refund_amount,
+            }
+        );
+    }
+
+    pub fn counter_offer(
+        &mut self,
+        refund_amount: Decimal,
+    ) {
+        self.check_offer(refund_amount);
+
+        self.counter_offer = Some(refund_amount);
+
+        Runtime::emit_event(
+            ReservationCounterOfferEvent {
+                reservation_id: self.id,
+                refund_amount: refund_amount,
+            }
+        );
+    }
+
+    fn check_offer(
+        &self,
+        refund_amount: Decimal,
     ) {
         assert!(
             self.status == ReservationStatus::Disputing,
             "Wrong status",
         );
         assert!(
-            refund_amount < Decimal::ZERO,
+            refund_amount >= Decimal::ZERO,
             "Negative refund not possible",
         );
         assert!(
             refund_amount <= self.vault.amount(),
             "Refund bigger than payment",
         );
+    }
 
+    // The customer accepts the owner offer or the owner accepts the customer counter offer; the
+    // expected refund_amount protects from an offer changed in the meantime
+    pub fn accept_offer(
+        &mut self,
+        accepted_by: Party,
+        expected_refund_amount: Decimal,
+    ) {
+        assert!(
+            self.status == ReservationStatus::Disputing,
+            "Wrong status",
+        );
+
+        let refund_amount = match accepted_by {
+            Party::Customer => self.refund_offer.expect("No offer to accept"),
+            Party::Owner => self.counter_offer.expect("No counter offer to accept"),
+        };
+        assert!(
+            refund_amount == expected_refund_amount,
+            "The offer has changed",
+        );
+
+        self.status = ReservationStatus::Settled;
         self.refund_amount = refund_amount;
         self.to_owner = self.vault.amount() - refund_amount;
 
         Runtime::emit_event(
-            ReservationRefundOfferEvent {
+            ReservationSettlementEvent {
                 reservation_id: self.id,
-                refund_amount: refund_amount,
+                accepted_by: accepted_by,
+                refund_amount: self.refund_amount,
+                to_owner: self.to_owner,
             }
         );
     }
 
+    // Either party can reject the other party offer and leave the decision to the arbitrators
+    pub fn escalate_to_arbitration(
+        &mut self,
+        escalated_by: Option<Party>,
+    ) {
+        assert!(
+            self.status == ReservationStatus::Disputing,
+            "Wrong status",
+        );
+
+        // The panel can only vote from now on
+        self.status = ReservationStatus::Arbitration;
+        self.arbitration_start_time = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+        self.start_panel_times();
+
+        Runtime::emit_event(
+            ReservationArbitrationEvent {
+                reservation_id: self.id,
+                escalated_by: escalated_by,
+            }
+        );
+    }
+
+    // Moves a dispute past its expired deadlines; returns true if the status has changed and the
+    // panelists who didn't vote before the default outcome was applied
+    pub fn apply_dispute_deadlines(
+        &mut self,
+        settings: &DisputeSettings,
+    ) -> (bool, Vec<u64>) {
+        let old_status = self.status;
+        let mut absent = vec![];
+        let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+
+        if self.status == ReservationStatus::Disputing && settings.owner_response_window.is_some() &&
+            now > self.dispute_start_time + settings.owner_response_window.unwrap() {
+            self.escalate_to_arbitration(None);
+            self.arbitration_start_time = self.dispute_start_time + settings.owner_response_window.unwrap();
+            self.start_panel_times();
+        }
+
+        if self.status == ReservationStatus::Arbitration && settings.arbitration_window.is_some() &&
+            now > self.arbitration_start_time + settings.arbitration_window.unwrap() {
+            self.status = ReservationStatus::DisputeTerminated;
+            self.refund_amount = match settings.default_outcome {
+                DefaultOutcome::FullRefund => self.vault.amount(),
+                DefaultOutcome::FullPayment => Decimal::ZERO,
+                DefaultOutcome::Split => self.vault.amount() / 2,
+            };
+            self.to_owner = self.vault.amount() - self.refund_amount;
+
+            let dispute_votes = &self.dispute_votes;
+            absent = self.panel.iter()
+                .filter(|arbitrator_id| !dispute_votes.contains_key(arbitrator_id))
+                .cloned()
+                .collect();
+
+            Runtime::emit_event(
+                DisputeDefaultOutcomeEvent {
+                    reservation_id: self.id,
+                    outcome: settings.default_outcome,
+                    refund_amount: self.refund_amount,
+                    to_owner: self.to_owner,
+                }
+            );
+        }
+
+        (self.status != old_status, absent)
+    }
+
     pub fn get_payment(
         &mut self,
         payment_delay: i64,
-    ) -> Bucket {
+        platform_fee: Option<Fee>,
+    ) -> (Bucket, Bucket, Bucket) {
         let old_status = self.status;
 
-        let payment = match self.status {
+        let mut payment = match self.status {
             ReservationStatus::Booked => {
                 let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
                 assert!(
@@ -280,10 +783,10 @@ impl Reservation {
                     "You can't get the payment now",
                 );
                 self.status = ReservationStatus::Completed;
-                self.vault.take_all()
+                self.vault.take(self.vault.amount() - self.security_deposit)
             },
 
-            ReservationStatus::DisputeTerminated => {
+            ReservationStatus::DisputeTerminated | ReservationStatus::Settled => {
                 let payment_amount = self.to_owner;
                 self.to_owner = Decimal::ZERO;
                 self.vault.take(payment_amount)
@@ -292,28 +795,128 @@ impl Reservation {
             _ => Runtime::panic("Wrong status".to_string())
         };
 
+        let fee_amount = match platform_fee {
+            Some(fee) => fee.compute(payment.amount()),
+            None => Decimal::ZERO,
+        };
+        let fee = payment.take(fee_amount);
+
+        // The owner receives the subsidy in proportion to the share of the price paid out, the rest
+        // goes back to the treasury
+        let subsidy_amount = match self.price > Decimal::ZERO {
+            true => cmp::min(self.subsidy.amount(), self.subsidy.amount() * (payment.amount() + fee_amount) / self.price),
+            false => self.subsidy.amount(),
+        };
+        let subsidy = self.subsidy.take_advanced(
+            subsidy_amount,
+            WithdrawStrategy::Rounded(RoundingMode::ToZero),
+        );
+
         Runtime::emit_event(
             ReservationGetPaymentEvent {
                 reservation_id: self.id,
                 old_status: old_status,
                 new_status: self.status,
                 payment_amount: payment.amount(),
+                fee_amount: fee_amount,
+                subsidy_amount: subsidy.amount(),
             }
         );
 
-        payment
+        payment.put(subsidy);
+
+        (payment, fee, self.subsidy.take_all())
+    }
+
+    // Returns the subsidy not paid to the owner
+    pub fn release_subsidy(
+        &mut self,
+    ) -> Bucket {
+        self.subsidy.take_all()
     }
 
+    // The vote_hash is the hash of the SBOR encoded (refund_percentage, salt) tuple
+    pub fn commit_vote(
+        &mut self,
+        arbitrator_id: u64,
+        vote_hash: Hash,
+        commit_window: i64,
+    ) {
+        assert!(
+            self.status == ReservationStatus::Arbitration,
+            "Wrong status",
+        );
+        assert!(
+            self.panel.contains(&arbitrator_id),
+            "You are not on the panel of this dispute",
+        );
+        assert!(
+            !self.dispute_votes.contains_key(&arbitrator_id),
+            "Vote already revealed",
+        );
+        let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+        assert!(
+            now <= self.panel_time(arbitrator_id) + commit_window,
+            "Commit window expired",
+        );
+
+        self.vote_commits.insert(arbitrator_id, vote_hash);
+
+        Runtime::emit_event(
+            DisputeVoteCommitEvent {
+                reservation_id: self.id,
+                arbitrator_id: arbitrator_id,
+            }
+        );
+    }
+
+    pub fn reveal_vote(
+        &mut self,
+        arbitrator_id: u64,
+        refund_percentage: Decimal,
+        salt: String,
+        commit_window: i64,
+        arbitration_fee: Option<Fee>,
+        loser_pays: bool,
+        aggregation: VoteAggregation,
+    ) -> Vec<(u64, Bucket)> {
+        // No vote is revealed while any panelist can still commit
+        let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+        assert!(
+            !self.commits_open(Some(commit_window), now),
+            "Votes can't be revealed yet",
+        );
+
+        let vote_hash = *self.vote_commits.get(&arbitrator_id).expect("No vote committed");
+        assert!(
+            vote_hash == hash(scrypto_encode(&(refund_percentage, salt)).unwrap()),
+            "The vote doesn't match the committed hash",
+        );
+        self.vote_commits.remove(&arbitrator_id);
+
+        self.dispute_vote(arbitrator_id, refund_percentage, Some(commit_window), arbitration_fee, loser_pays, aggregation)
+    }
+
+    // When the dispute terminates the arbitration fee is taken from the vault and split among the
+    // voters; by default both parties bear it in proportion to what they get, with loser_pays the
+    // party getting the smaller share pays it
     pub fn dispute_vote(
         &mut self,
         arbitrator_id: u64,
         refund_percentage: Decimal,
-        min_arbitrators: u16,
-    ) -> bool {
+        commit_window: Option<i64>,
+        arbitration_fee: Option<Fee>,
+        loser_pays: bool,
+        aggregation: VoteAggregation,
+    ) -> Vec<(u64, Bucket)> {
         assert!(
-            self.status == ReservationStatus::Disputing,
+            self.status == ReservationStatus::Arbitration,
             "Wrong status",
         );
+        assert!(
+            self.panel.contains(&arbitrator_id),
+            "You are not on the panel of this dispute",
+        );
 
         self.dispute_votes_sum += refund_percentage;
         let old_vote = self.dispute_votes.insert(arbitrator_id, refund_percentage);
@@ -322,33 +925,130 @@ impl Reservation {
         }
 
         let number_of_voters = self.dispute_votes.len();
+        let now = Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch;
+        let dispute_votes_sum = match self.commits_open(commit_window, now) {
+            true => None,
+            false => Some(self.dispute_votes_sum),
+        };
 
         Runtime::emit_event(
             DisputeVoteEvent {
                 reservation_id: self.id,
                 arbitrator_id: arbitrator_id,
                 number_of_voters: number_of_voters,
-                min_arbitrators: min_arbitrators,
-                dispute_votes_sum: self.dispute_votes_sum,
+                min_arbitrators: self.panel_size as u16,
+                dispute_votes_sum: dispute_votes_sum,
             }
         );
 
-        if number_of_voters >= min_arbitrators.into() {
-            self.status = ReservationStatus::DisputeTerminated;
-            self.refund_amount = (self.dispute_votes_sum / number_of_voters) * self.vault.amount();
-            self.to_owner = self.vault.amount() - self.refund_amount;
+        if number_of_voters < self.panel_size {
+            return vec![];
+        }
 
-            Runtime::emit_event(
-                DisputeVoteTerminatedEvent {
-                    reservation_id: self.id,
-                    refund_amount: self.refund_amount,
-                    to_owner: self.to_owner,
-                }
-            );
+        self.status = ReservationStatus::DisputeTerminated;
+
+        let total = self.vault.amount();
+        let fee_amount = match arbitration_fee {
+            Some(fee) => fee.compute(total),
+            None => Decimal::ZERO,
+        };
+        let outcome = aggregation.aggregate(self.dispute_votes.values().cloned().collect());
+        self.dispute_outcome = Some(outcome);
+        let refund_share = outcome / dec![100];
+        let refund_amount = total * refund_share;
+        let to_owner = total - refund_amount;
+
+        let customer_fee = match loser_pays {
+            false => fee_amount * refund_share,
+            true if refund_amount < to_owner => cmp::min(fee_amount, refund_amount),
+            true if refund_amount > to_owner => fee_amount - cmp::min(fee_amount, to_owner),
+            true => fee_amount / 2,
+        };
+        self.refund_amount = refund_amount - customer_fee;
+        self.to_owner = to_owner - (fee_amount - customer_fee);
+
+        Runtime::emit_event(
+            DisputeVoteTerminatedEvent {
+                reservation_id: self.id,
+                aggregation: aggregation,
+                refund_percentage: outcome,
+                refund_amount: self.refund_amount,
+                to_owner: self.to_owner,
+                arbitration_fee: fee_amount,
+            }
+        );
+
+        if fee_amount == Decimal::ZERO {
+            return vec![];
+        }
+
+        // The last voter gets the rounding leftovers
+        let mut fee = self.vault.take(fee_amount);
+        let share = fee_amount / number_of_voters;
+        let voters: Vec<u64> = self.dispute_votes.keys().cloned().collect();
+        let last_voter = voters[number_of_voters - 1];
+
+        let mut fees: Vec<(u64, Bucket)> = voters[..number_of_voters - 1].iter()
+            .map(|voter_id| (
+                *voter_id,
+                fee.take_advanced(share, WithdrawStrategy::Rounded(RoundingMode::ToZero)),
+            ))
+            .collect();
+        fees.push((last_voter, fee));
+
+        fees
+    }
+
+    pub fn review(
+        &mut self,
+        item_id: u64,
+        owner_id: u64,
+        reviewer: Party,
+        rating: u8,
+        content_hash: Hash,
+    ) {
+        assert!(
+            self.status == ReservationStatus::Completed ||
+            self.status == ReservationStatus::DisputeTerminated ||
+            self.status == ReservationStatus::Settled,
+            "Wrong status",
+        );
+        assert!(
+            rating >= 1 && rating <= 5,
+            "rating out of 1-5 range",
+        );
+
+        let (reviewer_id, reviewed_id) = match reviewer {
+            Party::Customer => {
+                assert!(
+                    !self.customer_reviewed,
+                    "Already reviewed",
+                );
+                self.customer_reviewed = true;
 
-            return true;
+                (self.customer_id, owner_id)
+            },
+            Party::Owner => {
+                assert!(
+                    !self.owner_reviewed,
+                    "Already reviewed",
+                );
+                self.owner_reviewed = true;
+
+                (owner_id, self.customer_id)
+            },
         };
 
-        false
+        Runtime::emit_event(
+            ReviewSubmittedEvent {
+                reservation_id: self.id,
+                item_id: item_id,
+                reviewer: reviewer,
+                reviewer_id: reviewer_id,
+                reviewed_id: reviewed_id,
+                rating: rating,
+                content_hash: content_hash,
+            }
+        );
     }
 }
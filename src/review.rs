@@ -0,0 +1,31 @@
+use scrypto::prelude::*;
+
+#[derive(Debug, ScryptoSbor, PartialEq, Clone, Copy)]
+pub enum Party {
+    Customer,
+    Owner,
+}
+
+#[derive(Debug, ScryptoSbor, Clone, Copy)]
+pub struct Rating {
+    pub count: u64,
+    pub average: Decimal,
+}
+
+impl Rating {
+
+    pub fn new() -> Rating {
+        Self {
+            count: 0,
+            average: Decimal::ZERO,
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        rating: u8,
+    ) {
+        self.average = (self.average * Decimal::from(self.count) + Decimal::from(rating)) / Decimal::from(self.count + 1);
+        self.count += 1;
+    }
+}
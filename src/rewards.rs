@@ -0,0 +1,41 @@
+use scrypto::prelude::*;
+
+// Funds of any coin accrued to a user or an arbitrator and not claimed yet
+#[derive(ScryptoSbor)]
+pub struct Rewards {
+    coins: Vec<ResourceAddress>,
+    vaults: KeyValueStore<ResourceAddress, Vault>,
+}
+
+impl Rewards {
+
+    pub fn new() -> Rewards {
+        Self {
+            coins: vec![],
+            vaults: KeyValueStore::new(),
+        }
+    }
+
+    pub fn deposit(
+        &mut self,
+        bucket: Bucket,
+    ) {
+        let coin = bucket.resource_address();
+
+        match self.vaults.get(&coin).is_some() {
+            true => self.vaults.get_mut(&coin).unwrap().put(bucket),
+            false => {
+                self.coins.push(coin);
+                self.vaults.insert(coin, Vault::with_bucket(bucket));
+            },
+        }
+    }
+
+    pub fn withdraw_all(
+        &mut self,
+    ) -> Vec<Bucket> {
+        self.coins.iter()
+            .map(|coin| self.vaults.get_mut(coin).unwrap().take_all())
+            .collect()
+    }
+}
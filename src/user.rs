@@ -1,10 +1,58 @@
 //TODO: check if there's a maximum number of items a user can own
 
 use scrypto::prelude::*;
+use crate::review::*;
+
+// How new users have to prove they are not already registered
+#[derive(Debug, ScryptoSbor, PartialEq, Clone, Copy)]
+pub enum RegistrationMode {
+    Open,
+    // A proof of the account owner role, one badge per account
+    Account,
+    // A proof of an identity badge issued by the component owner, one badge per identity
+    IdentityBadge(ResourceAddress),
+}
 
 #[derive(ScryptoSbor, ScryptoEvent)]
 pub struct NewUserEvent {
     user_id: u64,
+    referrer_id: Option<u64>,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ReferralRewardEvent {
+    referrer_id: u64,
+    user_id: u64,
+    coin: ResourceAddress,
+    amount: Decimal,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ProfileUpdateEvent {
+    user_id: u64,
+    display_name: String,
+    avatar_url: String,
+    contact_hash: Option<Hash>,
+    preferred_language: String,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct UserVerificationEvent {
+    user_id: u64,
+    verified: bool,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct GuardianUpdateEvent {
+    user_id: u64,
+    guardian_id: Option<u64>,
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct UserBadgeRecoveryEvent {
+    user_id: u64,
+    old_badge_id: u64,
+    new_badge_id: u64,
 }
 
 #[derive(Debug, ScryptoSbor, NonFungibleData)]
@@ -12,24 +60,137 @@ pub struct User {
     pub id: u64,
     #[mutable]
     pub owned_items: Vec<u64>,
+    pub referrer_id: Option<u64>,
+    #[mutable]
+    pub host_rating: Rating,
+    #[mutable]
+    pub guest_rating: Rating,
+    #[mutable]
+    pub display_name: String,
+    #[mutable]
+    pub avatar_url: String,
+    #[mutable]
+    pub contact_hash: Option<Hash>,
+    #[mutable]
+    pub preferred_language: String,
+    #[mutable]
+    pub verified: bool,
+    // User allowed to recover a lost badge
+    #[mutable]
+    pub guardian_id: Option<u64>,
 }
 
 impl User {
 
     pub fn new(
         id: u64,
+        referrer_id: Option<u64>,
     ) -> User {
 
         Runtime::emit_event(
             NewUserEvent {
                 user_id: id,
+                referrer_id: referrer_id,
             }
         );
 
         Self {
             id: id,
             owned_items: vec![],
+            referrer_id: referrer_id,
+            host_rating: Rating::new(),
+            guest_rating: Rating::new(),
+            display_name: String::new(),
+            avatar_url: String::new(),
+            contact_hash: None,
+            preferred_language: String::new(),
+            verified: false,
+            guardian_id: None,
         }
     }
-}
 
+    pub fn update_profile(
+        &mut self,
+        display_name: String,
+        avatar_url: String,
+        contact_hash: Option<Hash>,
+        preferred_language: String,
+    ) {
+        Runtime::emit_event(
+            ProfileUpdateEvent {
+                user_id: self.id,
+                display_name: display_name.clone(),
+                avatar_url: avatar_url.clone(),
+                contact_hash: contact_hash,
+                preferred_language: preferred_language.clone(),
+            }
+        );
+
+        self.display_name = display_name;
+        self.avatar_url = avatar_url;
+        self.contact_hash = contact_hash;
+        self.preferred_language = preferred_language;
+    }
+
+    pub fn set_verified(
+        &mut self,
+        verified: bool,
+    ) {
+        Runtime::emit_event(
+            UserVerificationEvent {
+                user_id: self.id,
+                verified: verified,
+            }
+        );
+
+        self.verified = verified;
+    }
+
+    pub fn referral_reward(
+        &self,
+        coin: ResourceAddress,
+        amount: Decimal,
+    ) {
+        Runtime::emit_event(
+            ReferralRewardEvent {
+                referrer_id: self.referrer_id.unwrap(),
+                user_id: self.id,
+                coin: coin,
+                amount: amount,
+            }
+        );
+    }
+
+    pub fn set_guardian(
+        &mut self,
+        guardian_id: Option<u64>,
+    ) {
+        assert!(
+            guardian_id != Some(self.id),
+            "You can't be your own guardian",
+        );
+
+        Runtime::emit_event(
+            GuardianUpdateEvent {
+                user_id: self.id,
+                guardian_id: guardian_id,
+            }
+        );
+
+        self.guardian_id = guardian_id;
+    }
+
+    pub fn badge_recovery(
+        &self,
+        old_badge_id: u64,
+        new_badge_id: u64,
+    ) {
+        Runtime::emit_event(
+            UserBadgeRecoveryEvent {
+                user_id: self.id,
+                old_badge_id: old_badge_id,
+                new_badge_id: new_badge_id,
+            }
+        );
+    }
+}
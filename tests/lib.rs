@@ -1,6 +1,24 @@
 use scrypto_test::prelude::*;
 
 use booking_system::booking_system::booking_system_test::*;
+use booking_system::dispute::*;
+use mock_pool::mock_pool_test::*;
+
+// Checks that a call failed with the given panic message
+fn assert_panic<T>(
+    result: Result<T, RuntimeError>,
+    message: &str,
+) {
+    match result {
+        Err(RuntimeError::ApplicationError(ApplicationError::PanicMessage(error))) => assert!(
+            error.contains(message),
+            "Unexpected panic message: {}",
+            error,
+        ),
+        Err(error) => panic!("Unexpected error: {:?}", error),
+        Ok(_) => panic!("The call didn't fail"),
+    }
+}
 
 #[test]
 fn test_booking_system() -> Result<(), RuntimeError> {
@@ -24,8 +42,1510 @@ fn test_booking_system() -> Result<(), RuntimeError> {
 
     // Create a user badge
     let user_badge_bucket1 = booking_system.new_user(
+        None,
+        None,
+        None,
+        &mut env
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_new_reservation_with_swap() -> Result<(), RuntimeError> {
+    let mut env = TestEnvironment::new();
+    env.disable_auth_module();
+    let package_address =
+        PackageFactory::compile_and_publish(this_package!(), &mut env, CompileProfile::Fast)?;
+    let mock_pool_package_address = PackageFactory::compile_and_publish(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/mock_pool"),
+        &mut env,
+        CompileProfile::Fast
+    )?;
+
+    // Create owner badge
+    let badge_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(0)
+        .mint_initial_supply(1, &mut env)?;
+    let badge_address = badge_bucket.resource_address(&mut env)?;
+
+    // Create the item coin and the coin held by the customer
+    let coin_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let coin_address = coin_bucket.resource_address(&mut env)?;
+    let other_coin_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(2000, &mut env)?;
+    let customer_bucket = other_coin_bucket.take(dec!(1000), &mut env)?;
+
+    // Instantiate a 1:1 pool and allow it in the BookingSystem
+    let pool = MockPool::new(
+        coin_bucket.into(),
+        other_coin_bucket.into(),
+        mock_pool_package_address,
+        &mut env
+    )?;
+
+    let mut booking_system = BookingSystem::new(
+        badge_address,
+        package_address,
+        &mut env
+    )?;
+    booking_system.add_swap_component(
+        ComponentAddress::new_or_panic(pool.0.0),
+        &mut env
+    )?;
+
+    // Create an item priced 10 coins per day
+    env.set_current_time(Instant::new(0));
+    let user_badge_bucket = booking_system.new_user(
+        None,
+        None,
+        None,
+        &mut env
+    )?;
+    booking_system.new_item(
+        user_badge_bucket.create_proof_of_all(&mut env)?,
+        86400,
+        coin_address,
+        0,
+        None,
+        &mut env
+    )?;
+    booking_system.add_or_modify_availability_interval(
+        user_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        true,
+        Some(dec!(10)),
+        &mut env
+    )?;
+
+    // Paying in the other coin books the reservation and returns the change in the item coin
+    let (reservation_bucket, change_bucket) = booking_system.new_reservation_with_swap(
+        user_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        2 * 86400,
+        customer_bucket.take(dec!(20), &mut env)?.into(),
+        ComponentAddress::new_or_panic(pool.0.0),
+        dec!(15),
+        None,
+        None,
+        &mut env
+    )?;
+    assert_eq!(reservation_bucket.amount(&mut env)?, dec!(1));
+    assert_eq!(change_bucket.resource_address(&mut env)?, coin_address);
+    // The pool returns 1000 * 20 / 1020 coins, 10 of them pay the reservation
+    assert_eq!(change_bucket.amount(&mut env)?, dec!("9.60784313725490196"));
+
+    // The swap must return at least min_output coins
+    let result = booking_system.new_reservation_with_swap(
+        user_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        2 * 86400,
+        3 * 86400,
+        customer_bucket.take(dec!(20), &mut env)?.into(),
+        ComponentAddress::new_or_panic(pool.0.0),
+        dec!(20),
+        None,
+        None,
+        &mut env
+    );
+    assert_panic(result, "Swap output below minimum");
+
+    // Only the allowed swap components can be used
+    let token_bucket_a = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let token_bucket_b = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let other_pool = MockPool::new(
+        token_bucket_a.into(),
+        token_bucket_b.into(),
+        mock_pool_package_address,
+        &mut env
+    )?;
+    let result = booking_system.new_reservation_with_swap(
+        user_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        2 * 86400,
+        3 * 86400,
+        customer_bucket.take(dec!(20), &mut env)?.into(),
+        ComponentAddress::new_or_panic(other_pool.0.0),
+        dec!(15),
+        None,
+        None,
+        &mut env
+    );
+    assert_panic(result, "Swap component not allowed");
+
+    Ok(())
+}
+
+// Books a 10 coins reservation, lets two arbitrators vote 30% and 60% with a 10% + 1 arbitration
+// fee and returns the refund, the owner payment and the fees claimed by the arbitrators
+fn run_arbitration(
+    loser_pays_arbitration: bool,
+) -> Result<(Decimal, Decimal, Decimal), RuntimeError> {
+    let mut env = TestEnvironment::new();
+    env.disable_auth_module();
+    let package_address =
+        PackageFactory::compile_and_publish(this_package!(), &mut env, CompileProfile::Fast)?;
+
+    // Create owner badge
+    let badge_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(0)
+        .mint_initial_supply(1, &mut env)?;
+    let badge_address = badge_bucket.resource_address(&mut env)?;
+
+    let coin_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let coin_address = coin_bucket.resource_address(&mut env)?;
+
+    let mut booking_system = BookingSystem::new(
+        badge_address,
+        package_address,
+        &mut env
+    )?;
+    booking_system.set_arbitration_fee(coin_address, dec!(10), dec!(1), &mut env)?;
+    booking_system.set_loser_pays_arbitration(loser_pays_arbitration, &mut env)?;
+    booking_system.set_min_arbitrators(2, &mut env)?;
+
+    // Create the owner, the customer and two arbitrators
+    env.set_current_time(Instant::new(0));
+    let owner_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    let customer_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    booking_system.new_user(None, None, None, &mut env)?;
+    booking_system.new_user(None, None, None, &mut env)?;
+    let arbitrator_badge_bucket1 = booking_system.get_arbitrator_badge(3, &mut env)?;
+    let arbitrator_badge_bucket2 = booking_system.get_arbitrator_badge(4, &mut env)?;
+
+    // Create an item priced 10 coins per day and book it for a day
+    booking_system.new_item(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        86400,
+        coin_address,
+        0,
+        None,
+        &mut env
+    )?;
+    booking_system.add_or_modify_availability_interval(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        true,
+        Some(dec!(10)),
+        &mut env
+    )?;
+    let (reservation_bucket, _change_bucket) = booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        2 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        None,
+        None,
+        &mut env
+    )?;
+
+    // The customer disputes the reservation and leaves the decision to the arbitrators
+    booking_system.start_dispute(
+        reservation_bucket.create_proof_of_all(&mut env)?,
+        &mut env
+    )?;
+    booking_system.reject_refund_offer(
+        reservation_bucket.create_proof_of_all(&mut env)?,
+        &mut env
+    )?;
+    booking_system.dispute_vote(
+        arbitrator_badge_bucket1.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        dec!(30),
+        &mut env
+    )?;
+    booking_system.dispute_vote(
+        arbitrator_badge_bucket2.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        dec!(60),
+        &mut env
+    )?;
+
+    let refund = booking_system.get_refund(reservation_bucket, &mut env)?.amount(&mut env)?;
+    let payment = booking_system.get_payment(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        &mut env
+    )?.unwrap().amount(&mut env)?;
+
+    let mut fees = Decimal::ZERO;
+    for arbitrator_badge_bucket in [arbitrator_badge_bucket1, arbitrator_badge_bucket2] {
+        let claimed = booking_system.claim_arbitration_fees(
+            arbitrator_badge_bucket.create_proof_of_all(&mut env)?,
+            &mut env
+        )?;
+        assert_eq!(claimed.len(), 1);
+        let fee = claimed[0].amount(&mut env)?;
+        assert_eq!(fee, dec!(1));
+        fees += fee;
+    }
+
+    Ok((refund, payment, fees))
+}
+
+#[test]
+fn test_arbitration_fee_mean_outcome() -> Result<(), RuntimeError> {
+    let (refund, payment, fees) = run_arbitration(false)?;
+
+    // The 2 coins fee is shared 45/55 like the 10 coins in the vault
+    assert_eq!(refund, dec!("3.6"));
+    assert_eq!(payment, dec!("4.4"));
+    assert_eq!(refund + payment + fees, dec!(10));
+
+    Ok(())
+}
+
+#[test]
+fn test_arbitration_fee_loser_pays_outcome() -> Result<(), RuntimeError> {
+    let (refund, payment, fees) = run_arbitration(true)?;
+
+    // The customer gets the smaller share and pays the whole fee
+    assert_eq!(refund, dec!("2.5"));
+    assert_eq!(payment, dec!("5.5"));
+    assert_eq!(refund + payment + fees, dec!(10));
+
+    Ok(())
+}
+
+#[test]
+fn test_platform_fee_to_treasury() -> Result<(), RuntimeError> {
+    let mut env = TestEnvironment::new();
+    env.disable_auth_module();
+    let package_address =
+        PackageFactory::compile_and_publish(this_package!(), &mut env, CompileProfile::Fast)?;
+
+    // Create owner badge
+    let badge_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(0)
+        .mint_initial_supply(1, &mut env)?;
+    let badge_address = badge_bucket.resource_address(&mut env)?;
+
+    let coin_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let coin_address = coin_bucket.resource_address(&mut env)?;
+
+    // Instantiate a BookingSystem component taking a 10% + 1 coin fee
+    let mut booking_system = BookingSystem::new(
+        badge_address,
+        package_address,
+        &mut env
+    )?;
+    booking_system.set_platform_fee(coin_address, dec!(10), dec!(1), &mut env)?;
+    booking_system.set_payment_delay(3600, &mut env)?;
+
+    // Create an item priced 10 coins per day and book it for a day
+    env.set_current_time(Instant::new(0));
+    let owner_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    let customer_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    booking_system.new_item(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        86400,
+        coin_address,
+        0,
+        None,
+        &mut env
+    )?;
+    booking_system.add_or_modify_availability_interval(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        true,
+        Some(dec!(10)),
+        &mut env
+    )?;
+    booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        2 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        None,
+        None,
+        &mut env
+    )?;
+
+    // The payment is released after the end of the reservation plus the payment delay
+    env.set_current_time(Instant::new(2 * 86400 + 3600));
+    let payment_bucket = booking_system.get_payment(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        &mut env
+    )?.unwrap();
+    assert_eq!(payment_bucket.amount(&mut env)?, dec!(8));
+
+    // The fee is in the treasury and the owner of the component can withdraw it
+    let treasury_bucket = booking_system.withdraw_treasury(coin_address, dec!(2), &mut env)?;
+    assert_eq!(treasury_bucket.resource_address(&mut env)?, coin_address);
+    assert_eq!(treasury_bucket.amount(&mut env)?, dec!(2));
+    assert_eq!(payment_bucket.amount(&mut env)? + treasury_bucket.amount(&mut env)?, dec!(10));
+
+    Ok(())
+}
+
+#[test]
+fn test_platform_coupon_subsidy() -> Result<(), RuntimeError> {
+    let mut env = TestEnvironment::new();
+    env.disable_auth_module();
+    let package_address =
+        PackageFactory::compile_and_publish(this_package!(), &mut env, CompileProfile::Fast)?;
+
+    // Create owner badge
+    let badge_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(0)
+        .mint_initial_supply(1, &mut env)?;
+    let badge_address = badge_bucket.resource_address(&mut env)?;
+
+    let coin_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let coin_address = coin_bucket.resource_address(&mut env)?;
+
+    // Instantiate a BookingSystem component taking a 10% + 1 coin fee and issue a 20% coupon
+    // whose discount is paid by the treasury
+    let mut booking_system = BookingSystem::new(
+        badge_address,
+        package_address,
+        &mut env
+    )?;
+    booking_system.set_platform_fee(coin_address, dec!(10), dec!(1), &mut env)?;
+    booking_system.new_coupon(
+        "PROMO".to_string(),
+        dec!(20),
+        dec!(0),
+        0,
+        10 * 86400,
+        None,
+        None,
+        vec![],
+        vec![],
+        &mut env
+    )?;
+
+    // Create an item priced 10 coins per day
+    env.set_current_time(Instant::new(0));
+    let owner_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    let customer_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    booking_system.new_item(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        86400,
+        coin_address,
+        0,
+        None,
+        &mut env
+    )?;
+    booking_system.add_or_modify_availability_interval(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        true,
+        Some(dec!(10)),
+        &mut env
+    )?;
+
+    // A first reservation without coupon puts its 2 coins fee in the treasury
+    booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        2 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        None,
+        None,
+        &mut env
+    )?;
+    env.set_current_time(Instant::new(2 * 86400));
+    booking_system.get_payment(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        &mut env
+    )?;
+
+    // The 2 coins discount is set aside from the treasury at booking
+    let (_reservation_bucket, change_bucket) = booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        3 * 86400,
+        4 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        Some("PROMO".to_string()),
+        None,
+        &mut env
+    )?;
+    assert_eq!(change_bucket.amount(&mut env)?, dec!(2));
+
+    // The treasury is now empty and can't cover the discount of another reservation
+    let result = booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        4 * 86400,
+        5 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        Some("PROMO".to_string()),
+        None,
+        &mut env
+    );
+    assert_panic(result, "Not enough funds in the treasury");
+
+    // The owner gets the 8 coins paid minus the 1.8 coins fee plus the 2 coins discount
+    env.set_current_time(Instant::new(4 * 86400));
+    let payment_bucket = booking_system.get_payment(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        2,
+        &mut env
+    )?.unwrap();
+    assert_eq!(payment_bucket.amount(&mut env)?, dec!("8.2"));
+
+    let treasury_bucket = booking_system.withdraw_treasury(coin_address, dec!("1.8"), &mut env)?;
+    assert_eq!(treasury_bucket.amount(&mut env)?, dec!("1.8"));
+
+    Ok(())
+}
+
+#[test]
+fn test_referral_rewards() -> Result<(), RuntimeError> {
+    let mut env = TestEnvironment::new();
+    env.disable_auth_module();
+    let package_address =
+        PackageFactory::compile_and_publish(this_package!(), &mut env, CompileProfile::Fast)?;
+
+    // Create owner badge
+    let badge_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(0)
+        .mint_initial_supply(1, &mut env)?;
+    let badge_address = badge_bucket.resource_address(&mut env)?;
+
+    let coin_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let coin_address = coin_bucket.resource_address(&mut env)?;
+
+    // Instantiate a BookingSystem component taking a 10% + 1 coin fee; referrers get half of the
+    // fee of the first reservation of the users they brought
+    let mut booking_system = BookingSystem::new(
+        badge_address,
+        package_address,
+        &mut env
+    )?;
+    booking_system.set_platform_fee(coin_address, dec!(10), dec!(1), &mut env)?;
+    booking_system.set_referral_program(dec!(50), 1, &mut env)?;
+
+    // Create the referrer, the owner and a customer referred by the first user
+    env.set_current_time(Instant::new(0));
+    let referrer_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    let owner_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    let customer_badge_bucket = booking_system.new_user(Some(1), None, None, &mut env)?;
+
+    // Create an item priced 10 coins per day
+    booking_system.new_item(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        86400,
+        coin_address,
+        0,
+        None,
+        &mut env
+    )?;
+    booking_system.add_or_modify_availability_interval(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        true,
+        Some(dec!(10)),
+        &mut env
+    )?;
+
+    // The customer completes two reservations, each paying a 2 coins fee
+    for reservation_id in 1..=2 {
+        let start_time = (2 * reservation_id as i64 - 1) * 86400;
+        booking_system.new_reservation(
+            customer_badge_bucket.create_proof_of_all(&mut env)?,
+            1,
+            start_time,
+            start_time + 86400,
+            coin_bucket.take(dec!(10), &mut env)?.into(),
+            None,
+            None,
+            &mut env
+        )?;
+        env.set_current_time(Instant::new(start_time + 86400));
+        let payment_bucket = booking_system.get_payment(
+            owner_badge_bucket.create_proof_of_all(&mut env)?,
+            1,
+            reservation_id,
+            &mut env
+        )?.unwrap();
+        assert_eq!(payment_bucket.amount(&mut env)?, dec!(8));
+    }
+
+    // Only the fee of the first reservation is shared with the referrer
+    let rewards = booking_system.claim_referral_rewards(
+        referrer_badge_bucket.create_proof_of_all(&mut env)?,
+        &mut env
+    )?;
+    assert_eq!(rewards.len(), 1);
+    assert_eq!(rewards[0].resource_address(&mut env)?, coin_address);
+    assert_eq!(rewards[0].amount(&mut env)?, dec!(1));
+
+    let treasury_bucket = booking_system.withdraw_treasury(coin_address, dec!(3), &mut env)?;
+    assert_eq!(treasury_bucket.amount(&mut env)?, dec!(3));
+
+    Ok(())
+}
+
+#[test]
+fn test_arbitrator_staking() -> Result<(), RuntimeError> {
+    let mut env = TestEnvironment::new();
+    env.disable_auth_module();
+    let package_address =
+        PackageFactory::compile_and_publish(this_package!(), &mut env, CompileProfile::Fast)?;
+
+    // Create owner badge
+    let badge_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(0)
+        .mint_initial_supply(1, &mut env)?;
+    let badge_address = badge_bucket.resource_address(&mut env)?;
+
+    let coin_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let coin_address = coin_bucket.resource_address(&mut env)?;
+    let staking_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let staking_address = staking_bucket.resource_address(&mut env)?;
+
+    // Arbitrators need a 100 tokens stake, lose 10% of it for a vote more than 10 points away
+    // from the outcome and get 5 tokens for a consistent vote
+    let mut booking_system = BookingSystem::new(
+        badge_address,
+        package_address,
+        &mut env
+    )?;
+    booking_system.set_staking_settings(staking_address, dec!(100), dec!(10), dec!(10), dec!(5), 3600, &mut env)?;
+    booking_system.set_min_arbitrators(3, &mut env)?;
+
+    // Create the owner, the customer and four arbitrators, only three of them staking
+    env.set_current_time(Instant::new(0));
+    let owner_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    let customer_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    let mut arbitrator_badge_buckets = vec![];
+    for user_id in 3..=6 {
+        booking_system.new_user(None, None, None, &mut env)?;
+        arbitrator_badge_buckets.push(booking_system.get_arbitrator_badge(user_id, &mut env)?);
+    }
+    for arbitrator_badge_bucket in &arbitrator_badge_buckets[..3] {
+        booking_system.stake(
+            arbitrator_badge_bucket.create_proof_of_all(&mut env)?,
+            staking_bucket.take(dec!(100), &mut env)?.into(),
+            &mut env
+        )?;
+    }
+
+    // Create an item priced 10 coins per day, book it and dispute the reservation
+    booking_system.new_item(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        86400,
+        coin_address,
+        0,
+        None,
+        &mut env
+    )?;
+    booking_system.add_or_modify_availability_interval(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        true,
+        Some(dec!(10)),
+        &mut env
+    )?;
+    let (reservation_bucket, _change_bucket) = booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        2 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        None,
+        None,
+        &mut env
+    )?;
+    booking_system.start_dispute(
+        reservation_bucket.create_proof_of_all(&mut env)?,
+        &mut env
+    )?;
+    booking_system.reject_refund_offer(
+        reservation_bucket.create_proof_of_all(&mut env)?,
+        &mut env
+    )?;
+
+    // The arbitrator without stake is not on the panel
+    let result = booking_system.dispute_vote(
+        arbitrator_badge_buckets[3].create_proof_of_all(&mut env)?,
+        1,
+        1,
+        dec!(50),
+        &mut env
+    );
+    assert_panic(result, "You are not on the panel of this dispute");
+
+    // The outcome is 60%: the third arbitrator is slashed 10 tokens, the other two get 5 each
+    for (arbitrator_badge_bucket, vote) in arbitrator_badge_buckets[..3].iter().zip([dec!(50), dec!(50), dec!(80)]) {
+        booking_system.dispute_vote(
+            arbitrator_badge_bucket.create_proof_of_all(&mut env)?,
+            1,
+            1,
+            vote,
+            &mut env
+        )?;
+    }
+
+    // Stakes can only be withdrawn after the cooldown
+    booking_system.request_unstake(
+        arbitrator_badge_buckets[0].create_proof_of_all(&mut env)?,
+        dec!(105),
+        &mut env
+    )?;
+    booking_system.request_unstake(
+        arbitrator_badge_buckets[2].create_proof_of_all(&mut env)?,
+        dec!(90),
+        &mut env
+    )?;
+    let result = booking_system.unstake(
+        arbitrator_badge_buckets[0].create_proof_of_all(&mut env)?,
+        &mut env
+    );
+    assert_panic(result, "You can't unstake now");
+
+    env.set_current_time(Instant::new(3600));
+    let unstaked_bucket = booking_system.unstake(
+        arbitrator_badge_buckets[0].create_proof_of_all(&mut env)?,
+        &mut env
+    )?;
+    assert_eq!(unstaked_bucket.resource_address(&mut env)?, staking_address);
+    assert_eq!(unstaked_bucket.amount(&mut env)?, dec!(105));
+    let unstaked_bucket = booking_system.unstake(
+        arbitrator_badge_buckets[2].create_proof_of_all(&mut env)?,
         &mut env
     )?;
+    assert_eq!(unstaked_bucket.amount(&mut env)?, dec!(90));
+
+    Ok(())
+}
+
+#[test]
+fn test_negative_loyalty_points() -> Result<(), RuntimeError> {
+    let mut env = TestEnvironment::new();
+    env.disable_auth_module();
+    let package_address =
+        PackageFactory::compile_and_publish(this_package!(), &mut env, CompileProfile::Fast)?;
+
+    // Create owner badge
+    let badge_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(0)
+        .mint_initial_supply(1, &mut env)?;
+    let badge_address = badge_bucket.resource_address(&mut env)?;
+
+    let coin_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let coin_address = coin_bucket.resource_address(&mut env)?;
+
+    // Each completed reservation is worth 10 points, each point is worth 0.1 coins
+    let mut booking_system = BookingSystem::new(
+        badge_address,
+        package_address,
+        &mut env
+    )?;
+    booking_system.set_platform_fee(coin_address, dec!(10), dec!(1), &mut env)?;
+    booking_system.set_loyalty_earn_rate(dec!(10), None, &mut env)?;
+    booking_system.set_loyalty_burn_rate(coin_address, dec!("0.1"), &mut env)?;
+
+    // Create an item priced 10 coins per day
+    env.set_current_time(Instant::new(0));
+    let owner_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    let customer_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    booking_system.new_item(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        86400,
+        coin_address,
+        0,
+        None,
+        &mut env
+    )?;
+    booking_system.add_or_modify_availability_interval(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        true,
+        Some(dec!(10)),
+        &mut env
+    )?;
+
+    // A completed reservation credits the customer with 10 points
+    booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        2 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        None,
+        None,
+        &mut env
+    )?;
+    env.set_current_time(Instant::new(2 * 86400));
+    booking_system.get_payment(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        &mut env
+    )?;
+
+    // Redeeming a negative amount would credit the customer with free points
+    let result = booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        3 * 86400,
+        4 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        None,
+        Some(dec!(-1000)),
+        &mut env
+    );
+    assert_panic(result, "loyalty_points must be positive");
+
+    Ok(())
+}
+
+#[test]
+fn test_loyalty_points() -> Result<(), RuntimeError> {
+    let mut env = TestEnvironment::new();
+    env.disable_auth_module();
+    let package_address =
+        PackageFactory::compile_and_publish(this_package!(), &mut env, CompileProfile::Fast)?;
+
+    // Create owner badge
+    let badge_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(0)
+        .mint_initial_supply(1, &mut env)?;
+    let badge_address = badge_bucket.resource_address(&mut env)?;
+
+    let coin_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let coin_address = coin_bucket.resource_address(&mut env)?;
+
+    // Each completed reservation is worth 10 points expiring after a day, each point is worth
+    // 0.1 coins
+    let mut booking_system = BookingSystem::new(
+        badge_address,
+        package_address,
+        &mut env
+    )?;
+    booking_system.set_platform_fee(coin_address, dec!(10), dec!(1), &mut env)?;
+    booking_system.set_loyalty_earn_rate(dec!(10), Some(86400), &mut env)?;
+    booking_system.set_loyalty_burn_rate(coin_address, dec!("0.1"), &mut env)?;
+
+    // Create an item priced 10 coins per day
+    env.set_current_time(Instant::new(0));
+    let owner_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    let customer_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    booking_system.new_item(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        86400,
+        coin_address,
+        0,
+        None,
+        &mut env
+    )?;
+    booking_system.add_or_modify_availability_interval(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        true,
+        Some(dec!(10)),
+        &mut env
+    )?;
+
+    // A completed reservation credits the customer with 10 points
+    booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        2 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        None,
+        None,
+        &mut env
+    )?;
+    env.set_current_time(Instant::new(2 * 86400));
+    booking_system.get_payment(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        &mut env
+    )?;
+
+    // 5 points give a 0.5 coins discount
+    let (_reservation_bucket, change_bucket) = booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        3 * 86400,
+        4 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        None,
+        Some(dec!(5)),
+        &mut env
+    )?;
+    assert_eq!(change_bucket.amount(&mut env)?, dec!("0.5"));
+
+    // The other 5 points are claimed as tokens and deposited back, they don't expire anymore
+    let loyalty_points_bucket = booking_system.claim_loyalty_points(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        &mut env
+    )?;
+    assert_eq!(loyalty_points_bucket.amount(&mut env)?, dec!(5));
+    booking_system.deposit_loyalty_points(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        loyalty_points_bucket,
+        &mut env
+    )?;
+
+    // The second completed reservation credits 10 more points, they expire at 5 * 86400
+    env.set_current_time(Instant::new(4 * 86400));
+    booking_system.get_payment(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        2,
+        &mut env
+    )?;
+
+    // After the expiry only the 5 deposited points are left
+    env.set_current_time(Instant::new(6 * 86400));
+    let (_reservation_bucket, change_bucket) = booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        7 * 86400,
+        8 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        None,
+        Some(dec!(5)),
+        &mut env
+    )?;
+    assert_eq!(change_bucket.amount(&mut env)?, dec!("0.5"));
+
+    let result = booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        9 * 86400,
+        10 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        None,
+        Some(dec!(1)),
+        &mut env
+    );
+    assert_panic(result, "Not enough loyalty points");
+
+    Ok(())
+}
+
+#[test]
+fn test_moderation() -> Result<(), RuntimeError> {
+    let mut env = TestEnvironment::new();
+    env.disable_auth_module();
+    let package_address =
+        PackageFactory::compile_and_publish(this_package!(), &mut env, CompileProfile::Fast)?;
+
+    // Create owner badge
+    let badge_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(0)
+        .mint_initial_supply(1, &mut env)?;
+    let badge_address = badge_bucket.resource_address(&mut env)?;
+
+    let coin_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let coin_address = coin_bucket.resource_address(&mut env)?;
+
+    let mut booking_system = BookingSystem::new(
+        badge_address,
+        package_address,
+        &mut env
+    )?;
+
+    // Create two items priced 10 coins per day owned by different users
+    env.set_current_time(Instant::new(0));
+    let owner_badge_bucket1 = booking_system.new_user(None, None, None, &mut env)?;
+    let owner_badge_bucket2 = booking_system.new_user(None, None, None, &mut env)?;
+    let customer_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    for (item_id, owner_badge_bucket) in [(1, &owner_badge_bucket1), (2, &owner_badge_bucket2)] {
+        booking_system.new_item(
+            owner_badge_bucket.create_proof_of_all(&mut env)?,
+            86400,
+            coin_address,
+            0,
+            None,
+            &mut env
+        )?;
+        booking_system.add_or_modify_availability_interval(
+            owner_badge_bucket.create_proof_of_all(&mut env)?,
+            item_id,
+            86400,
+            true,
+            Some(dec!(10)),
+            &mut env
+        )?;
+    }
+
+    // Book the first item twice and dispute the second reservation
+    let (reservation_bucket1, _change_bucket) = booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        2 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        None,
+        None,
+        &mut env
+    )?;
+    let (reservation_bucket2, _change_bucket) = booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        2 * 86400,
+        3 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        None,
+        None,
+        &mut env
+    )?;
+    booking_system.start_dispute(
+        reservation_bucket2.create_proof_of_all(&mut env)?,
+        &mut env
+    )?;
+
+    // Delisting the item cancels both reservations, the customer gets full refunds
+    booking_system.delist_item(1, 1, &mut env)?;
+    let refund_bucket = booking_system.get_refund(reservation_bucket1, &mut env)?;
+    assert_eq!(refund_bucket.amount(&mut env)?, dec!(10));
+    let refund_bucket = booking_system.get_refund(reservation_bucket2, &mut env)?;
+    assert_eq!(refund_bucket.amount(&mut env)?, dec!(10));
+
+    // The items of a suspended owner can't be booked
+    booking_system.suspend_user(2, 1, &mut env)?;
+    let result = booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        2,
+        86400,
+        2 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        None,
+        None,
+        &mut env
+    );
+    assert_panic(result, "Item owner suspended");
+
+    Ok(())
+}
+
+#[test]
+fn test_arbitrator_slashed_at_default_outcome() -> Result<(), RuntimeError> {
+    let mut env = TestEnvironment::new();
+    env.disable_auth_module();
+    let package_address =
+        PackageFactory::compile_and_publish(this_package!(), &mut env, CompileProfile::Fast)?;
+
+    // Create owner badge
+    let badge_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(0)
+        .mint_initial_supply(1, &mut env)?;
+    let badge_address = badge_bucket.resource_address(&mut env)?;
+
+    let coin_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let coin_address = coin_bucket.resource_address(&mut env)?;
+    let staking_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let staking_address = staking_bucket.resource_address(&mut env)?;
+
+    // The arbitrators have an hour to decide, then the amount is split in half
+    let mut booking_system = BookingSystem::new(
+        badge_address,
+        package_address,
+        &mut env
+    )?;
+    booking_system.set_staking_settings(staking_address, dec!(100), dec!(10), dec!(10), dec!(5), 0, &mut env)?;
+    booking_system.set_dispute_settings(None, None, None, Some(3600), None, None, DefaultOutcome::Split, &mut env)?;
+
+    // Create the owner, the customer and a staking arbitrator
+    env.set_current_time(Instant::new(0));
+    let owner_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    let customer_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    booking_system.new_user(None, None, None, &mut env)?;
+    let arbitrator_badge_bucket = booking_system.get_arbitrator_badge(3, &mut env)?;
+    booking_system.stake(
+        arbitrator_badge_bucket.create_proof_of_all(&mut env)?,
+        staking_bucket.take(dec!(100), &mut env)?.into(),
+        &mut env
+    )?;
+
+    // Create an item priced 10 coins per day, book it and dispute the reservation
+    booking_system.new_item(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        86400,
+        coin_address,
+        0,
+        None,
+        &mut env
+    )?;
+    booking_system.add_or_modify_availability_interval(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        true,
+        Some(dec!(10)),
+        &mut env
+    )?;
+    let (reservation_bucket, _change_bucket) = booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        2 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        None,
+        None,
+        &mut env
+    )?;
+    booking_system.start_dispute(
+        reservation_bucket.create_proof_of_all(&mut env)?,
+        &mut env
+    )?;
+    booking_system.reject_refund_offer(
+        reservation_bucket.create_proof_of_all(&mut env)?,
+        &mut env
+    )?;
+
+    // Nobody votes: the default outcome is applied and the arbitrator loses 10 tokens
+    env.set_current_time(Instant::new(3601));
+    let refund_bucket = booking_system.get_refund(reservation_bucket, &mut env)?;
+    assert_eq!(refund_bucket.amount(&mut env)?, dec!(5));
+
+    let result = booking_system.request_unstake(
+        arbitrator_badge_bucket.create_proof_of_all(&mut env)?,
+        dec!(100),
+        &mut env
+    );
+    assert_panic(result, "Wrong amount");
+    booking_system.request_unstake(
+        arbitrator_badge_bucket.create_proof_of_all(&mut env)?,
+        dec!(90),
+        &mut env
+    )?;
+    let unstaked_bucket = booking_system.unstake(
+        arbitrator_badge_bucket.create_proof_of_all(&mut env)?,
+        &mut env
+    )?;
+    assert_eq!(unstaked_bucket.amount(&mut env)?, dec!(90));
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_reveal_voting() -> Result<(), RuntimeError> {
+    let mut env = TestEnvironment::new();
+    env.disable_auth_module();
+    let package_address =
+        PackageFactory::compile_and_publish(this_package!(), &mut env, CompileProfile::Fast)?;
+
+    // Create owner badge
+    let badge_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(0)
+        .mint_initial_supply(1, &mut env)?;
+    let badge_address = badge_bucket.resource_address(&mut env)?;
+
+    let coin_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let coin_address = coin_bucket.resource_address(&mut env)?;
+
+    // Two panelists have an hour to commit their votes and two hours to reveal them
+    let mut booking_system = BookingSystem::new(
+        badge_address,
+        package_address,
+        &mut env
+    )?;
+    booking_system.set_dispute_settings(None, None, None, None, Some(7200), Some(3600), DefaultOutcome::Split, &mut env)?;
+    booking_system.set_min_arbitrators(2, &mut env)?;
+
+    // Create the owner, the customer and two arbitrators
+    env.set_current_time(Instant::new(0));
+    let owner_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    let customer_badge_bucket = booking_system.new_user(None, None, None, &mut env)?;
+    booking_system.new_user(None, None, None, &mut env)?;
+    booking_system.new_user(None, None, None, &mut env)?;
+    let arbitrator_badge_bucket1 = booking_system.get_arbitrator_badge(3, &mut env)?;
+    let arbitrator_badge_bucket2 = booking_system.get_arbitrator_badge(4, &mut env)?;
+
+    // Create an item priced 10 coins per day, book it and dispute the reservation
+    booking_system.new_item(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        86400,
+        coin_address,
+        0,
+        None,
+        &mut env
+    )?;
+    booking_system.add_or_modify_availability_interval(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        true,
+        Some(dec!(10)),
+        &mut env
+    )?;
+    let (reservation_bucket, _change_bucket) = booking_system.new_reservation(
+        customer_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        2 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        None,
+        None,
+        &mut env
+    )?;
+    booking_system.start_dispute(
+        reservation_bucket.create_proof_of_all(&mut env)?,
+        &mut env
+    )?;
+    booking_system.reject_refund_offer(
+        reservation_bucket.create_proof_of_all(&mut env)?,
+        &mut env
+    )?;
+
+    // Public votes are not allowed
+    let result = booking_system.dispute_vote(
+        arbitrator_badge_bucket1.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        dec!(40),
+        &mut env
+    );
+    assert_panic(result, "Votes must be committed and revealed");
+
+    // The first arbitrator commits a vote, it can't be revealed within the commit window
+    booking_system.commit_vote(
+        arbitrator_badge_bucket1.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        hash(scrypto_encode(&(dec!(40), "salt1".to_string())).unwrap()),
+        &mut env
+    )?;
+    env.set_current_time(Instant::new(100));
+    let result = booking_system.reveal_vote(
+        arbitrator_badge_bucket1.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        dec!(40),
+        "salt1".to_string(),
+        &mut env
+    );
+    assert_panic(result, "Votes can't be revealed yet");
+
+    // The second arbitrator is too late to commit
+    env.set_current_time(Instant::new(3601));
+    let result = booking_system.commit_vote(
+        arbitrator_badge_bucket2.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        hash(scrypto_encode(&(dec!(100), "salt2".to_string())).unwrap()),
+        &mut env
+    );
+    assert_panic(result, "Commit window expired");
+
+    // The revealed vote must match the committed hash
+    let result = booking_system.reveal_vote(
+        arbitrator_badge_bucket1.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        dec!(40),
+        "salt2".to_string(),
+        &mut env
+    );
+    assert_panic(result, "The vote doesn't match the committed hash");
+    booking_system.reveal_vote(
+        arbitrator_badge_bucket1.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        dec!(40),
+        "salt1".to_string(),
+        &mut env
+    )?;
+
+    // The missing vote keeps the dispute open
+    let result = booking_system.get_payment(
+        owner_badge_bucket.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        &mut env
+    );
+    assert_panic(result, "Wrong status");
+
+    // After the vote window the second arbitrator is replaced by a new one
+    env.set_current_time(Instant::new(7201));
+    booking_system.new_user(None, None, None, &mut env)?;
+    let arbitrator_badge_bucket3 = booking_system.get_arbitrator_badge(5, &mut env)?;
+    booking_system.replace_absent_panelists(1, 1, &mut env)?;
+
+    // The new panelist has a commit window of their own, then the dispute is terminated
+    booking_system.commit_vote(
+        arbitrator_badge_bucket3.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        hash(scrypto_encode(&(dec!(60), "salt3".to_string())).unwrap()),
+        &mut env
+    )?;
+    env.set_current_time(Instant::new(7201 + 3601));
+    booking_system.reveal_vote(
+        arbitrator_badge_bucket3.create_proof_of_all(&mut env)?,
+        1,
+        1,
+        dec!(60),
+        "salt3".to_string(),
+        &mut env
+    )?;
+
+    let refund_bucket = booking_system.get_refund(reservation_bucket, &mut env)?;
+    assert_eq!(refund_bucket.amount(&mut env)?, dec!(5));
+
+    Ok(())
+}
+
+#[test]
+fn test_coupons() -> Result<(), RuntimeError> {
+    let mut env = TestEnvironment::new();
+    env.disable_auth_module();
+    let package_address =
+        PackageFactory::compile_and_publish(this_package!(), &mut env, CompileProfile::Fast)?;
+
+    // Create owner badge
+    let badge_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(0)
+        .mint_initial_supply(1, &mut env)?;
+    let badge_address = badge_bucket.resource_address(&mut env)?;
+
+    let coin_bucket = ResourceBuilder::new_fungible(OwnerRole::None)
+        .divisibility(18)
+        .mint_initial_supply(1000, &mut env)?;
+    let coin_address = coin_bucket.resource_address(&mut env)?;
+
+    let mut booking_system = BookingSystem::new(
+        badge_address,
+        package_address,
+        &mut env
+    )?;
+
+    // Create two items priced 10 coins per day owned by different users
+    env.set_current_time(Instant::new(0));
+    let owner_badge_bucket1 = booking_system.new_user(None, None, None, &mut env)?;
+    let owner_badge_bucket2 = booking_system.new_user(None, None, None, &mut env)?;
+    let customer_badge_bucket1 = booking_system.new_user(None, None, None, &mut env)?;
+    let customer_badge_bucket2 = booking_system.new_user(None, None, None, &mut env)?;
+    for (item_id, owner_badge_bucket) in [(1, &owner_badge_bucket1), (2, &owner_badge_bucket2)] {
+        booking_system.new_item(
+            owner_badge_bucket.create_proof_of_all(&mut env)?,
+            86400,
+            coin_address,
+            0,
+            None,
+            &mut env
+        )?;
+        booking_system.add_or_modify_availability_interval(
+            owner_badge_bucket.create_proof_of_all(&mut env)?,
+            item_id,
+            86400,
+            true,
+            Some(dec!(10)),
+            &mut env
+        )?;
+    }
+
+    // A host can only issue coupons for their own items
+    let result = booking_system.new_host_coupon(
+        owner_badge_bucket1.create_proof_of_all(&mut env)?,
+        "HOST".to_string(),
+        dec!(50),
+        dec!(0),
+        100,
+        1000,
+        Some(2),
+        Some(1),
+        vec![2],
+        &mut env
+    );
+    assert_panic(result, "You are not the owner of item 2");
+
+    // A 50% coupon for the first item, valid from 100 to 1000, two uses, one per user
+    booking_system.new_host_coupon(
+        owner_badge_bucket1.create_proof_of_all(&mut env)?,
+        "HOST".to_string(),
+        dec!(50),
+        dec!(0),
+        100,
+        1000,
+        Some(2),
+        Some(1),
+        vec![1],
+        &mut env
+    )?;
+    let result = booking_system.new_reservation(
+        customer_badge_bucket1.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        2 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        Some("HOST".to_string()),
+        None,
+        &mut env
+    );
+    assert_panic(result, "Coupon not valid now");
+
+    env.set_current_time(Instant::new(100));
+    let (_reservation_bucket, change_bucket) = booking_system.new_reservation(
+        customer_badge_bucket1.create_proof_of_all(&mut env)?,
+        1,
+        86400,
+        2 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        Some("HOST".to_string()),
+        None,
+        &mut env
+    )?;
+    assert_eq!(change_bucket.amount(&mut env)?, dec!(5));
+
+    let result = booking_system.new_reservation(
+        customer_badge_bucket1.create_proof_of_all(&mut env)?,
+        1,
+        2 * 86400,
+        3 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        Some("HOST".to_string()),
+        None,
+        &mut env
+    );
+    assert_panic(result, "Coupon usage limit reached for this user");
+
+    let result = booking_system.new_reservation(
+        customer_badge_bucket2.create_proof_of_all(&mut env)?,
+        2,
+        86400,
+        2 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        Some("HOST".to_string()),
+        None,
+        &mut env
+    );
+    assert_panic(result, "Coupon not valid for this item");
+
+    booking_system.new_reservation(
+        customer_badge_bucket2.create_proof_of_all(&mut env)?,
+        1,
+        2 * 86400,
+        3 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        Some("HOST".to_string()),
+        None,
+        &mut env
+    )?;
+    let result = booking_system.new_reservation(
+        owner_badge_bucket2.create_proof_of_all(&mut env)?,
+        1,
+        3 * 86400,
+        4 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        Some("HOST".to_string()),
+        None,
+        &mut env
+    );
+    assert_panic(result, "Coupon usage limit reached");
+
+    // Only the creator can disable a host coupon
+    let result = booking_system.disable_host_coupon(
+        owner_badge_bucket2.create_proof_of_all(&mut env)?,
+        "HOST".to_string(),
+        &mut env
+    );
+    assert_panic(result, "You are not the creator of this coupon");
+    booking_system.new_host_coupon(
+        owner_badge_bucket1.create_proof_of_all(&mut env)?,
+        "OPEN".to_string(),
+        dec!(0),
+        dec!(1),
+        0,
+        1000,
+        None,
+        None,
+        vec![],
+        &mut env
+    )?;
+    booking_system.disable_host_coupon(
+        owner_badge_bucket1.create_proof_of_all(&mut env)?,
+        "OPEN".to_string(),
+        &mut env
+    )?;
+    let result = booking_system.new_reservation(
+        customer_badge_bucket2.create_proof_of_all(&mut env)?,
+        1,
+        4 * 86400,
+        5 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        Some("OPEN".to_string()),
+        None,
+        &mut env
+    );
+    assert_panic(result, "Coupon disabled");
+
+    // A platform coupon limited to the items of the second owner
+    booking_system.new_coupon(
+        "PLATFORM".to_string(),
+        dec!(10),
+        dec!(0),
+        0,
+        1000,
+        None,
+        None,
+        vec![],
+        vec![2],
+        &mut env
+    )?;
+    let result = booking_system.new_reservation(
+        customer_badge_bucket2.create_proof_of_all(&mut env)?,
+        1,
+        4 * 86400,
+        5 * 86400,
+        coin_bucket.take(dec!(10), &mut env)?.into(),
+        Some("PLATFORM".to_string()),
+        None,
+        &mut env
+    );
+    assert_panic(result, "Coupon not valid for this host");
 
     Ok(())
 }
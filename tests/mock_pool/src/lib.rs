@@ -0,0 +1,58 @@
+use scrypto::prelude::*;
+
+// Minimal constant product pool exposing the swap interface expected by
+// BookingSystem::new_reservation_with_swap; meant for tests only
+#[blueprint]
+mod mock_pool {
+
+    struct MockPool {
+        vault_a: Vault,
+        vault_b: Vault,
+    }
+
+    impl MockPool {
+
+        pub fn new(
+            bucket_a: Bucket,
+            bucket_b: Bucket,
+        ) -> Global<MockPool> {
+            assert!(
+                bucket_a.resource_address() != bucket_b.resource_address(),
+                "The pool needs two different coins",
+            );
+
+            Self {
+                vault_a: Vault::with_bucket(bucket_a),
+                vault_b: Vault::with_bucket(bucket_b),
+            }
+            .instantiate()
+            .prepare_to_globalize(OwnerRole::None)
+            .globalize()
+        }
+
+        pub fn swap(
+            &mut self,
+            input: Bucket,
+        ) -> Bucket {
+            let (input_vault, output_vault) = match input.resource_address() == self.vault_a.resource_address() {
+                true => (&mut self.vault_a, &mut self.vault_b),
+                false => {
+                    assert!(
+                        input.resource_address() == self.vault_b.resource_address(),
+                        "Wrong coin",
+                    );
+
+                    (&mut self.vault_b, &mut self.vault_a)
+                }
+            };
+
+            let output_amount = output_vault.amount() * input.amount() / (input_vault.amount() + input.amount());
+            input_vault.put(input);
+
+            output_vault.take_advanced(
+                output_amount,
+                WithdrawStrategy::Rounded(RoundingMode::ToZero),
+            )
+        }
+    }
+}